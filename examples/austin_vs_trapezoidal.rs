@@ -0,0 +1,92 @@
+//! Compares the `Austin` and `Trapezoidal` motion profiles
+//!
+//! Prints the time each of them needs per step, using their default fixed-point
+//! types, and how far their delays deviate from the exact step times computed
+//! by `ExactTrapezoidal`. Run it in release mode to get meaningful timings:
+//!
+//! ``` bash
+//! cargo run --release --features std --example austin_vs_trapezoidal
+//! ```
+
+use std::{hint::black_box, time::Instant};
+
+use fixed::traits::Fixed;
+use ramp_maker::{
+    sim::Accuracy, Austin, ExactTrapezoidal, MotionProfile, Trapezoidal,
+};
+
+const TARGET_ACCEL: f64 = 6000.0; // steps per second^2
+const MAX_VELOCITY: f64 = 1000.0; // steps per second
+const NUM_STEPS: u32 = 2000;
+
+// The number of motions to run when measuring the time per step.
+const RUNS: u32 = 1000;
+
+fn main() {
+    println!("Time per step (default fixed-point types):");
+    print_time_per_step("Austin", || {
+        Austin::new(ramp_maker::austin::DefaultNum::from_num(TARGET_ACCEL))
+    });
+    print_time_per_step("Trapezoidal", || {
+        Trapezoidal::new(ramp_maker::trapezoidal::DefaultNum::from_num(
+            TARGET_ACCEL,
+        ))
+    });
+
+    println!();
+    println!("Delay error, compared to the exact step times (f64):");
+    print_delay_error("Austin", Austin::new(TARGET_ACCEL));
+    print_delay_error("Trapezoidal", Trapezoidal::new(TARGET_ACCEL));
+}
+
+fn print_time_per_step<Profile>(name: &str, new: impl Fn() -> Profile)
+where
+    Profile: MotionProfile,
+    Profile::Velocity: Fixed,
+{
+    let max_velocity = Profile::Velocity::from_num(MAX_VELOCITY);
+
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let mut profile = new();
+        profile.enter_position_mode(max_velocity, NUM_STEPS);
+        for delay in profile.delays() {
+            black_box(delay);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let steps = RUNS as f64 * NUM_STEPS as f64;
+    let ns_per_step = elapsed.as_nanos() as f64 / steps;
+    println!("  {:<12} {:>8.2} ns", name, ns_per_step);
+}
+
+fn print_delay_error<Profile>(name: &str, mut profile: Profile)
+where
+    Profile: MotionProfile<Velocity = f64, Delay = f64>,
+{
+    let mut reference = ExactTrapezoidal::new(TARGET_ACCEL);
+    reference.enter_position_mode(MAX_VELOCITY, NUM_STEPS);
+    profile.enter_position_mode(MAX_VELOCITY, NUM_STEPS);
+
+    let accuracy = Accuracy::compare(&mut reference, &mut profile);
+
+    // The first and last steps of each ramp are the least accurate, so report
+    // them separately.
+    let ramp_ends = 5;
+    let max_error_without_ends = accuracy.steps[ramp_ends..]
+        .iter()
+        .take(accuracy.steps.len() - 2 * ramp_ends)
+        .map(|step| step.delay_error.abs())
+        .fold(0.0, f64::max);
+
+    println!(
+        "  {:<12} {:>7.3}% max, {:>7.3}% without the first/last {} steps, \
+        {:+.3} ms total",
+        name,
+        accuracy.max_delay_error() * 100.0,
+        max_error_without_ends * 100.0,
+        ramp_ends,
+        accuracy.time_error * 1000.0,
+    );
+}
@@ -5,4 +5,5 @@ set -e
 export RUSTFLAGS="-D warnings"
 
 cargo test --verbose &&
+cargo test --all-features --verbose &&
 cargo doc
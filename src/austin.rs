@@ -0,0 +1,460 @@
+//! Austin motion profile
+//!
+//! See [`Austin`].
+
+use core::ops;
+
+use az::Az as _;
+
+use crate::{util::traits::Sqrt, MotionProfile};
+
+/// Austin motion profile
+///
+/// Generates an approximation of a trapezoidal ramp, following the algorithm
+/// from David Austin's article "Generate stepper-motor speed profiles in real
+/// time", which is also the basis of Atmel's application note AVR446:
+/// [https://www.embedded.com/generate-stepper-motor-speed-profiles-in-real-time/](https://www.embedded.com/generate-stepper-motor-speed-profiles-in-real-time/)
+///
+/// Each delay is computed from the previous one, using the recurrence
+/// `c_n = c_{n-1} - 2 * c_{n-1} / (4n + 1)`, where `n` counts the steps on the
+/// ramp. Unlike [`Trapezoidal`], this doesn't require any multiplication with
+/// the target acceleration per step, just a division by a small integer. This
+/// makes it a good fit for 8- and 16-bit microcontrollers, where multiplying
+/// fixed-point numbers is expensive. The `austin_vs_trapezoidal` example in the
+/// repository compares the time per step and the accuracy of both.
+///
+/// This implementation makes the following simplifications:
+/// - The unit of time used is left to the user (see "Unit of Time" below), so
+///   the timer frequency `f` from the article is ignored.
+/// - The initial velocity is assumed to be zero.
+///
+/// Create an instance of this struct using [`Austin::new`], then use the API
+/// defined by [`MotionProfile`] (which this struct implements) to generate the
+/// acceleration ramp.
+///
+/// # Acceleration Ramp
+///
+/// This struct will generate a trapezoidal acceleration ramp with the following
+/// attributes:
+/// - The velocity will always be equal to or less than the maximum velocity
+///   passed to [`MotionProfile::enter_position_mode`].
+/// - While ramping up or down, the acceleration will be an approximation
+///   of the target acceleration passed to the constructor. The approximation
+///   is least accurate for the first few steps.
+/// - Deceleration mirrors acceleration, which means the ramp down takes exactly
+///   as many steps as the ramp up.
+///
+/// # Unit of Time
+///
+/// This code is agnostic on which unit of time is used. If you provide the
+/// target acceleration and maximum velocity in steps per second, the unit of
+/// the delay returned will be seconds.
+///
+/// This allows you to pass the parameters in steps per number of timer counts
+/// for the timer you're using, completely eliminating any conversion overhead
+/// for the delay. This is particularly useful for smaller fixed-point types, as
+/// delays in timer counts tend to make good use of their range.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent the
+/// target acceleration, maximum velocity, and delays per step. It is set to a
+/// 64-bit fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other type from the
+/// `fixed` crate. Please note that you need to enable support for `f32`/`f64`
+/// explicitly. Check out the section on Cargo features from the documentation
+/// in the root module for more information.
+///
+/// [`Trapezoidal`]: crate::Trapezoidal
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Austin<Num = DefaultNum> {
+    delay_min: Option<Num>,
+    delay_initial: Num,
+    delay_prev: Num,
+
+    /// The number of steps on the ramp, i.e. the number of steps it would take
+    /// to reach the current velocity from stand-still
+    ramp_steps: u32,
+
+    /// The delay of the last step on the ramp
+    ramp_delay: Num,
+
+    steps_left: u32,
+
+    /// Whether a step has been made since the motion profile was last idle
+    moving: bool,
+}
+
+impl<Num> Austin<Num>
+where
+    Num: Copy
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Sqrt,
+    u32: az::Cast<Num>,
+{
+    /// Create a new instance of `Austin`
+    ///
+    /// Accepts the target acceleration in steps per (unit of time)^2 as an
+    /// argument. It must not be zero. See the struct documentation for
+    /// information about units of time.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `target_accel` is zero.
+    pub fn new(target_accel: Num) -> Self {
+        let two = Num::one() + Num::one();
+
+        // Based on equations [7] and [15] in the article. The factor 0.676
+        // compensates for the inaccuracy of the recurrence for the first step.
+        let correction = 676.az::<Num>() / 1000.az::<Num>();
+        let delay_initial = correction * (two / target_accel).sqrt();
+
+        Self {
+            delay_min: None,
+            delay_initial,
+            delay_prev: delay_initial,
+
+            ramp_steps: 0,
+            ramp_delay: delay_initial,
+
+            steps_left: 0,
+            moving: false,
+        }
+    }
+}
+
+// Needed for the `MotionProfile` test suite in `crate::util::testing`.
+#[cfg(test)]
+impl Default for Austin<f32> {
+    fn default() -> Self {
+        Self::new(6000.0)
+    }
+}
+
+impl<Num> MotionProfile for Austin<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Div<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        if self.is_idle() {
+            self.moving = false;
+        }
+
+        self.delay_min = if max_velocity.is_zero() {
+            None
+        } else {
+            Some(max_velocity.inv())
+        };
+
+        self.steps_left = num_steps;
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        let two = Num::one() + Num::one();
+
+        let delay_next = match RampMode::compute(self) {
+            RampMode::Idle => {
+                return None;
+            }
+            RampMode::RampUp { delay_next } => {
+                self.ramp_steps += 1;
+                self.ramp_delay = delay_next;
+                delay_next
+            }
+            RampMode::Plateau { delay_next } => delay_next,
+            RampMode::RampDown => {
+                let delay_next = self.ramp_delay;
+
+                self.ramp_steps -= 1;
+
+                // Inverse of equation [13] in the article, to compute the
+                // delay of the previous step on the ramp.
+                self.ramp_delay = match self.ramp_steps {
+                    0 => self.delay_initial,
+                    n => {
+                        let denominator = (4 * n - 1).az::<Num>();
+                        delay_next + two * delay_next / denominator
+                    }
+                };
+
+                delay_next
+            }
+        };
+
+        self.delay_prev = delay_next;
+        self.steps_left = self.steps_left.saturating_sub(1);
+        self.moving = true;
+
+        Some(delay_next)
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.steps_left
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        if !self.moving || self.is_idle() {
+            return Num::zero();
+        }
+
+        self.delay_prev.inv()
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(RampMode::compute(self), RampMode::Idle)
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        // Ramping down takes as many steps as there are on the ramp.
+        self.ramp_steps
+    }
+}
+
+/// The default numeric type used by [`Austin`]
+pub type DefaultNum = fixed::FixedU64<typenum::U32>;
+
+enum RampMode<Num> {
+    Idle,
+    RampUp { delay_next: Num },
+    Plateau { delay_next: Num },
+    RampDown,
+}
+
+impl<Num> RampMode<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Div<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    fn compute(profile: &Austin<Num>) -> Self {
+        let steps_left = profile.steps_left;
+        let ramp_steps = profile.ramp_steps;
+
+        if steps_left == 0 && ramp_steps == 0 {
+            return Self::Idle;
+        }
+
+        // Ramping down takes exactly as many steps as there are on the ramp.
+        // If the target step is that close, we need to decelerate.
+        if steps_left <= ramp_steps {
+            return Self::RampDown;
+        }
+
+        let delay_min = match profile.delay_min {
+            Some(delay_min) => delay_min,
+            None => {
+                // No minimum delay means someone set max velocity to zero.
+                return if ramp_steps == 0 {
+                    Self::Idle
+                } else {
+                    Self::RampDown
+                };
+            }
+        };
+
+        // If the last step on the ramp is too fast, someone lowered the
+        // maximum velocity.
+        if ramp_steps > 0 && profile.ramp_delay < delay_min {
+            return Self::RampDown;
+        }
+
+        // Accelerating adds a step to the ramp, which then needs an additional
+        // step to ramp down. If that doesn't fit, we need to keep the current
+        // velocity for a step.
+        if steps_left == ramp_steps + 1 {
+            return Self::Plateau {
+                delay_next: profile.delay_prev,
+            };
+        }
+
+        // Equation [13] in the article.
+        let delay_next = match ramp_steps {
+            0 => profile.delay_initial,
+            n => {
+                let two = Num::one() + Num::one();
+                let denominator = (4 * n + 1).az::<Num>();
+
+                profile.ramp_delay - two * profile.ramp_delay / denominator
+            }
+        };
+
+        if delay_next < delay_min {
+            return Self::Plateau {
+                delay_next: delay_min,
+            };
+        }
+
+        Self::RampUp { delay_next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{
+        sim::Accuracy, Austin, ExactTrapezoidal, MotionProfile, Trapezoidal,
+    };
+
+    #[test]
+    fn austin_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<Austin<f32>>();
+    }
+
+    #[test]
+    fn austin_should_generate_ramp_with_approximate_target_acceleration() {
+        let target_accel = 6000.0;
+        let mut austin = Austin::new(target_accel);
+
+        // Make the ramp so short that it becomes triangular. This makes testing
+        // a bit easier, as we don't have to deal with the plateau.
+        let num_steps = 100;
+        austin.enter_position_mode(1000.0, num_steps);
+
+        for (i, accel) in austin.accelerations::<f32>().enumerate() {
+            let around_start = i < 5;
+            let around_end = i as u32 > num_steps - 5;
+            let around_peak = i as u32 == num_steps / 2 - 1;
+
+            // There are some inaccuracies at various points, which we
+            // accept. The rest of the ramp is much more accurate.
+            if !around_start && !around_end && !around_peak {
+                assert_abs_diff_eq!(
+                    accel.abs(),
+                    target_accel,
+                    epsilon = target_accel * 0.05,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn austin_should_come_to_stop_on_target_step() {
+        for num_steps in 1..50 {
+            let mut austin = Austin::<f64>::new(6000.0);
+            austin.enter_position_mode(1000.0, num_steps);
+
+            let delays: Vec<f64> = austin.delays().collect();
+            assert_eq!(delays.len() as u32, num_steps);
+
+            // The ramp must be symmetric.
+            assert_abs_diff_eq!(
+                delays[0],
+                delays[delays.len() - 1],
+                epsilon = 1e-12,
+            );
+        }
+    }
+
+    #[test]
+    fn austin_should_adapt_to_changes_in_max_velocity() {
+        let mut austin = Austin::<f64>::new(6000.0);
+
+        // Accelerate to maximum velocity.
+        austin.enter_position_mode(1000.0, 10_000);
+        let mut prev_delay = austin.next_delay().unwrap();
+        loop {
+            let delay = austin.next_delay().unwrap();
+            if delay == prev_delay {
+                break;
+            }
+            assert!(delay < prev_delay);
+            prev_delay = delay;
+        }
+        assert_abs_diff_eq!(1.0 / prev_delay, 1000.0, epsilon = 0.001);
+
+        // Decelerate to a lower maximum velocity.
+        austin.enter_position_mode(500.0, 10_000);
+        loop {
+            let delay = austin.next_delay().unwrap();
+            if delay == prev_delay {
+                break;
+            }
+            assert!(delay > prev_delay);
+            prev_delay = delay;
+        }
+        assert_abs_diff_eq!(1.0 / prev_delay, 500.0, epsilon = 0.001);
+
+        // Decelerate to stand-still.
+        austin.enter_position_mode(0.0, 10_000);
+        for delay in austin.delays() {
+            assert!(delay > prev_delay);
+            prev_delay = delay;
+        }
+    }
+
+    #[test]
+    fn austin_should_be_more_accurate_than_trapezoidal() {
+        let austin = accuracy(Austin::new(6000.0));
+        let trapezoidal = accuracy(Trapezoidal::new(6000.0));
+
+        // The first steps are inaccurate for both, but much less so for this
+        // profile, thanks to the correction of the initial delay.
+        assert!(austin.max_delay_error() < trapezoidal.max_delay_error());
+
+        // Once the first steps are done, this profile is very accurate.
+        let ramp_ends = 5;
+        let steps = &austin.steps[ramp_ends..austin.steps.len() - ramp_ends];
+        for step in steps {
+            assert!(step.delay_error.abs() < 0.002);
+        }
+    }
+
+    #[test]
+    fn austin_should_work_with_16_bit_fixed_point_numbers() {
+        use fixed::types::U16F16;
+
+        // Parameters are given in steps per millisecond, which results in
+        // delays in milliseconds that make good use of the numeric range.
+        let mut austin = Austin::new(U16F16::from_num(0.006));
+
+        let num_steps = 2000;
+        austin.enter_position_mode(U16F16::from_num(1), num_steps);
+
+        let delays: Vec<U16F16> = austin.delays().collect();
+        assert_eq!(delays.len() as u32, num_steps);
+
+        let delay_min = delays.iter().copied().min().unwrap();
+        assert_abs_diff_eq!(delay_min.to_num::<f32>(), 1.0, epsilon = 0.001);
+    }
+
+    // Compares a motion profile to the exact step times, for a motion that
+    // reaches its maximum velocity.
+    fn accuracy(
+        mut profile: impl MotionProfile<Velocity = f64, Delay = f64>,
+    ) -> Accuracy {
+        let max_velocity = 1000.0;
+        let num_steps = 2000;
+
+        let mut reference = ExactTrapezoidal::new(6000.0);
+        reference.enter_position_mode(max_velocity, num_steps);
+        profile.enter_position_mode(max_velocity, num_steps);
+
+        Accuracy::compare(&mut reference, &mut profile)
+    }
+}
@@ -0,0 +1,789 @@
+//! Axis with absolute position and travel limits
+//!
+//! See [`Axis`].
+
+use core::ops;
+
+use az::Az as _;
+use num_traits::{Inv, Zero};
+
+use crate::{Direction, MotionProfile};
+
+/// Wraps a [`MotionProfile`], keeping track of the absolute position
+///
+/// Motion profiles only deal with relative motions in a single direction. This
+/// struct keeps track of the absolute position of the axis, determines the
+/// direction of each step, and optionally enforces travel limits.
+///
+/// Create an instance of this struct using [`Axis::new`], then use
+/// [`Axis::move_to`] or [`Axis::run`] to start a motion. Call
+/// [`Axis::next_step`] to get the direction and delay of each step. For
+/// interactive control from an input device, use [`Axis::jog`].
+///
+/// # Travel Limits
+///
+/// If limits have been set using [`Axis::set_limits`], [`Axis::move_to`]
+/// rejects any target outside of them, while [`Axis::move_to_clamped`] clamps
+/// the target to them. [`Axis::run`] moves towards the limit in the given
+/// direction and, using the ramp of the motion profile, comes to a stop
+/// exactly on it.
+///
+/// Limits only apply to motions that are started after they have been set.
+///
+/// # Changing Direction
+///
+/// If a new motion is started while the axis is moving, and the new target
+/// lies behind the axis, or is too close to stop in time, the axis first comes
+/// to a stop, then starts the new motion. The motion profile's
+/// [`MotionProfile::steps_to_stop`] is used to make that decision.
+///
+/// # Backlash Compensation
+///
+/// If [`Axis::set_backlash`] has been called, extra steps are inserted
+/// whenever the direction of motion reverses, to take up the play in the
+/// mechanism. Those steps are executed at a constant, safe velocity, before the
+/// motion in the new direction starts, and don't count towards the position.
+///
+/// # Encoder Feedback
+///
+/// If the axis has an encoder, report its measurements using
+/// [`Axis::report_encoder`]. If the measured position deviates too much from
+/// the commanded one, the position is corrected and the current motion is
+/// re-targeted, to make up for lost steps. Larger deviations are flagged as a
+/// stall, and bring the axis to a stop. The thresholds for both are configured
+/// using [`Axis::set_encoder_thresholds`].
+///
+/// Since backlash compensation steps don't count towards the position, the
+/// encoder should measure the position of the load, not of the motor.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Axis<Profile: MotionProfile> {
+    profile: Profile,
+    position: i32,
+    direction: Direction,
+    limits: Option<(i32, i32)>,
+    max_velocity: Option<Profile::Velocity>,
+    target: Option<(i32, Profile::Velocity)>,
+    pending: Option<(i32, Profile::Velocity)>,
+    correction_threshold: u32,
+    stall_threshold: u32,
+    backlash: Option<(u32, Profile::Delay)>,
+    play: Option<u32>,
+}
+
+impl<Profile> Axis<Profile>
+where
+    Profile: MotionProfile,
+    Profile::Velocity: Copy,
+    Profile::Delay: Copy,
+{
+    /// Create a new instance of `Axis`
+    ///
+    /// The axis starts at position zero, without any limits.
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            position: 0,
+            direction: Direction::Forward,
+            limits: None,
+            max_velocity: None,
+            target: None,
+            pending: None,
+            correction_threshold: 0,
+            stall_threshold: u32::MAX,
+            backlash: None,
+            play: None,
+        }
+    }
+
+    /// Return the current position
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Override the current position
+    ///
+    /// Use this to establish a reference position, after homing the axis, for
+    /// example.
+    pub fn set_position(&mut self, position: i32) {
+        self.position = position;
+    }
+
+    /// Limit the travel of the axis to `min..=max`
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `min` is larger than `max`.
+    pub fn set_limits(&mut self, min: i32, max: i32) {
+        assert!(min <= max, "Lower limit must not exceed upper limit");
+        self.limits = Some((min, max));
+    }
+
+    /// Remove the travel limits
+    pub fn clear_limits(&mut self) {
+        self.limits = None;
+    }
+
+    /// Return the travel limits, if any have been set
+    pub fn limits(&self) -> Option<(i32, i32)> {
+        self.limits
+    }
+
+    /// Compensate for the given amount of backlash
+    ///
+    /// Inserts `steps` extra steps at `velocity` on every reversal of the
+    /// direction of motion. It is assumed that the play has been taken up in
+    /// the direction of the next motion, so no steps are inserted before that.
+    pub fn set_backlash(&mut self, steps: u32, velocity: Profile::Velocity)
+    where
+        Profile::Velocity: Inv<Output = Profile::Delay>,
+    {
+        self.backlash = Some((steps, velocity.inv()));
+        self.play = None;
+    }
+
+    /// Disable backlash compensation
+    pub fn clear_backlash(&mut self) {
+        self.backlash = None;
+        self.play = None;
+    }
+
+    /// Configure how deviations reported by the encoder are handled
+    ///
+    /// Deviations of up to `correction` steps are tolerated. Larger deviations
+    /// are corrected, unless they exceed `stall` steps, which is considered a
+    /// stall. By default, every deviation is corrected, and none is considered
+    /// a stall.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `correction` is larger than `stall`.
+    pub fn set_encoder_thresholds(&mut self, correction: u32, stall: u32) {
+        assert!(
+            correction <= stall,
+            "Correction threshold must not exceed stall threshold"
+        );
+        self.correction_threshold = correction;
+        self.stall_threshold = stall;
+    }
+
+    /// Report the position measured by an encoder
+    ///
+    /// The measured position must be in steps, in the same coordinate system
+    /// as [`Axis::position`]. See [`Correction`] for how the deviation from
+    /// the commanded position is handled.
+    pub fn report_encoder(&mut self, measured: i32) -> Correction {
+        let error = measured.wrapping_sub(self.position);
+        let deviation = error.unsigned_abs();
+
+        if deviation <= self.correction_threshold {
+            return Correction::InTolerance;
+        }
+
+        self.position = measured;
+
+        if deviation > self.stall_threshold {
+            self.stop();
+            return Correction::Stall { error };
+        }
+
+        // Re-target the current motion, unless we're about to start another
+        // one, which is going to take the new position into account anyway.
+        if self.pending.is_none() {
+            if let Some((target, max_velocity)) = self.target {
+                self.start(target, max_velocity);
+            }
+        }
+
+        Correction::Corrected { error }
+    }
+
+    /// Move to the given absolute position
+    ///
+    /// Returns an error, and leaves any current motion unaffected, if the
+    /// target lies outside of the travel limits.
+    pub fn move_to(
+        &mut self,
+        target: i32,
+        max_velocity: Profile::Velocity,
+    ) -> Result<(), OutOfRange> {
+        if let Some((min, max)) = self.limits {
+            if target < min || target > max {
+                return Err(OutOfRange);
+            }
+        }
+
+        self.start(target, max_velocity);
+        Ok(())
+    }
+
+    /// Move to the given absolute position, clamped to the travel limits
+    pub fn move_to_clamped(
+        &mut self,
+        target: i32,
+        max_velocity: Profile::Velocity,
+    ) {
+        let target = match self.limits {
+            Some((min, max)) => target.clamp(min, max),
+            None => target,
+        };
+
+        self.start(target, max_velocity);
+    }
+
+    /// Move in the given direction, until stopped or a limit is reached
+    pub fn run(
+        &mut self,
+        direction: Direction,
+        max_velocity: Profile::Velocity,
+    ) {
+        let target = match (direction, self.limits) {
+            (Direction::Forward, Some((_, max))) => max,
+            (Direction::Backward, Some((min, _))) => min,
+            (Direction::Forward, None) => i32::MAX,
+            (Direction::Backward, None) => i32::MIN,
+        };
+
+        self.start(target, max_velocity);
+    }
+
+    /// Jog at the given signed velocity
+    ///
+    /// Meant to be called repeatedly, as often as every step, with input from
+    /// a hand wheel or joystick. The sign of the velocity selects the
+    /// direction: positive values move forward, negative ones backward. The
+    /// axis tracks the commanded velocity within the acceleration limit of the
+    /// motion profile, changes direction by coming to a stop first, and comes
+    /// to a stop, if the velocity is zero (or NaN).
+    ///
+    /// The velocity can be of any type that can be cast to the velocity type
+    /// of the motion profile. This allows jogging motion profiles that use an
+    /// unsigned fixed-point type, like the default ones in this library, using
+    /// the signed type of the same size (`I32F32` for `U32F32`, for example).
+    ///
+    /// Like [`Axis::run`], this respects the travel limits.
+    ///
+    /// The jerk-limited motion profiles in this library don't change the
+    /// velocity of a motion in progress, so they only track changes of
+    /// direction. To track the velocity, use a motion profile that can, like
+    /// [`Trapezoidal`].
+    ///
+    /// [`Trapezoidal`]: crate::Trapezoidal
+    pub fn jog<Velocity>(&mut self, velocity: Velocity)
+    where
+        Velocity: Copy
+            + PartialOrd
+            + Zero
+            + ops::Neg<Output = Velocity>
+            + az::Cast<Profile::Velocity>,
+    {
+        let (direction, speed) = if velocity > Velocity::zero() {
+            (Direction::Forward, velocity)
+        } else if velocity < Velocity::zero() {
+            (Direction::Backward, -velocity)
+        } else {
+            self.stop();
+            return;
+        };
+
+        self.run(direction, speed.az::<Profile::Velocity>());
+    }
+
+    /// Come to a stop as soon as possible
+    ///
+    /// Cancels any motion that would have been started after the current one.
+    pub fn stop(&mut self) {
+        self.target = None;
+        self.pending = None;
+        if let Some(max_velocity) = self.max_velocity {
+            self.profile.enter_position_mode(max_velocity, 0);
+        }
+    }
+
+    /// Return the direction and delay of the next step
+    ///
+    /// Returns `None`, if the axis has come to a stop.
+    pub fn next_step(&mut self) -> Option<(Direction, Profile::Delay)> {
+        loop {
+            if let Some(delay) = self.take_up_backlash() {
+                return Some((self.direction, delay));
+            }
+
+            if let Some(delay) = self.profile.next_delay() {
+                self.position += self.direction.sign();
+                return Some((self.direction, delay));
+            }
+
+            let (target, max_velocity) = self.pending.take()?;
+            self.start(target, max_velocity);
+        }
+    }
+
+    /// Indicate whether the axis is at a stand-still
+    pub fn is_idle(&self) -> bool {
+        self.profile.is_idle() && self.pending.is_none()
+    }
+
+    /// Access the motion profile
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Release the motion profile
+    pub fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    /// Return the delay of a backlash compensation step, if one is needed
+    fn take_up_backlash(&mut self) -> Option<Profile::Delay> {
+        let (backlash, delay) = self.backlash?;
+        if self.profile.is_idle() {
+            return None;
+        }
+
+        // The play is measured from the point where the mechanism engages in
+        // backward direction. If we don't know it yet, we assume that it is
+        // engaged in the direction of the first motion.
+        let play = self.play.get_or_insert(match self.direction {
+            Direction::Forward => backlash,
+            Direction::Backward => 0,
+        });
+        *play = (*play).min(backlash);
+
+        match self.direction {
+            Direction::Forward if *play < backlash => *play += 1,
+            Direction::Backward if *play > 0 => *play -= 1,
+            _ => return None,
+        }
+
+        Some(delay)
+    }
+
+    fn start(&mut self, target: i32, max_velocity: Profile::Velocity) {
+        self.target = Some((target, max_velocity));
+        self.max_velocity = Some(max_velocity);
+        self.pending = None;
+
+        let direction = if target >= self.position {
+            Direction::Forward
+        } else {
+            Direction::Backward
+        };
+        let distance = target.abs_diff(self.position);
+
+        if self.profile.is_idle() {
+            self.direction = direction;
+            self.profile.enter_position_mode(max_velocity, distance);
+            return;
+        }
+
+        // We're already moving. If the target lies ahead and we can still
+        // stop on it, we can just change the current motion. Otherwise, we
+        // need to stop first.
+        let can_stop_on_target = distance >= self.profile.steps_to_stop();
+        if direction == self.direction && can_stop_on_target {
+            self.profile.enter_position_mode(max_velocity, distance);
+        } else {
+            self.profile.enter_position_mode(max_velocity, 0);
+            self.pending = Some((target, max_velocity));
+        }
+    }
+}
+
+/// Error returned by [`Axis::move_to`], if the target is outside the limits
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OutOfRange;
+
+/// The result of reporting an encoder measurement
+///
+/// Returned by [`Axis::report_encoder`]. The error is the measured position
+/// minus the commanded position, in steps.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[must_use]
+pub enum Correction {
+    /// The deviation is within tolerance, and nothing was changed
+    InTolerance,
+
+    /// The position was corrected, and the current motion was re-targeted
+    Corrected {
+        /// The deviation that was corrected
+        error: i32,
+    },
+
+    /// The deviation is too large, indicating a stall
+    ///
+    /// The position was corrected, and the axis is coming to a stop.
+    Stall {
+        /// The deviation that was detected
+        error: i32,
+    },
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Direction, Flat, MotionProfile as _, Quintic, Sinusoidal, Trapezoidal,
+    };
+
+    use super::{Axis, Correction, OutOfRange};
+
+    fn positions<Profile>(axis: &mut Axis<Profile>) -> Vec<i32>
+    where
+        Profile: crate::MotionProfile<Velocity = f32, Delay = f32>,
+    {
+        let mut positions = Vec::new();
+        while axis.next_step().is_some() {
+            positions.push(axis.position());
+        }
+        positions
+    }
+
+    #[test]
+    fn axis_should_track_absolute_position() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+
+        axis.move_to(300, 1000.0).unwrap();
+        assert_eq!(positions(&mut axis).len(), 300);
+        assert_eq!(axis.position(), 300);
+
+        axis.move_to(-200, 1000.0).unwrap();
+        assert_eq!(axis.next_step().map(|(d, _)| d), Some(Direction::Backward));
+        assert_eq!(positions(&mut axis).len(), 499);
+        assert_eq!(axis.position(), -200);
+        assert!(axis.is_idle());
+    }
+
+    #[test]
+    fn axis_should_reject_targets_outside_limits() {
+        let mut axis = Axis::new(Flat::new());
+        axis.set_limits(-100, 100);
+
+        assert_eq!(axis.move_to(101, 1000.0), Err(OutOfRange));
+        assert_eq!(axis.move_to(-101, 1000.0), Err(OutOfRange));
+        assert!(axis.next_step().is_none());
+
+        axis.move_to(100, 1000.0).unwrap();
+        positions(&mut axis);
+        assert_eq!(axis.position(), 100);
+    }
+
+    #[test]
+    fn axis_should_clamp_targets_to_limits() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_limits(-100, 100);
+
+        axis.move_to_clamped(1000, 1000.0);
+        positions(&mut axis);
+        assert_eq!(axis.position(), 100);
+
+        axis.move_to_clamped(-1000, 1000.0);
+        positions(&mut axis);
+        assert_eq!(axis.position(), -100);
+    }
+
+    #[test]
+    fn axis_should_stop_exactly_on_limit_when_running() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_limits(-500, 500);
+
+        axis.run(Direction::Forward, 2000.0);
+        let positions = positions(&mut axis);
+
+        assert_eq!(axis.position(), 500);
+        assert!(positions.iter().all(|&position| position <= 500));
+        assert!(axis.profile().is_idle());
+    }
+
+    #[test]
+    fn axis_should_stop_exactly_on_limit_with_planned_profiles() {
+        let mut axis = Axis::new(Quintic::new(6000.0));
+        axis.set_limits(-500, 500);
+
+        axis.move_to(200, 1000.0).unwrap();
+        for _ in 0..100 {
+            axis.next_step();
+        }
+        axis.run(Direction::Forward, 1000.0);
+        let positions = positions(&mut axis);
+
+        assert_eq!(axis.position(), 500);
+        assert!(positions.iter().all(|&position| position <= 500));
+        assert!(axis.profile().is_idle());
+    }
+
+    #[test]
+    fn axis_should_stop_when_requested_with_planned_profiles() {
+        let mut axis = Axis::new(Sinusoidal::new(6000.0));
+
+        axis.run(Direction::Forward, 1000.0);
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        let steps_to_stop = axis.profile().steps_to_stop();
+        axis.stop();
+        let steps = positions(&mut axis).len() as u32;
+
+        assert!(steps <= steps_to_stop);
+        assert!(axis.is_idle());
+        assert_eq!(axis.position(), 300 + steps as i32);
+    }
+
+    #[test]
+    fn axis_should_stop_before_reversing() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_limits(-1000, 1000);
+
+        axis.run(Direction::Forward, 2000.0);
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        let steps_to_stop = axis.profile().steps_to_stop();
+        axis.run(Direction::Backward, 2000.0);
+        let positions = positions(&mut axis);
+
+        let turning_point = positions.iter().copied().max().unwrap();
+        assert!(turning_point <= 300 + steps_to_stop as i32);
+        assert!(turning_point < 1000);
+        assert_eq!(axis.position(), -1000);
+    }
+
+    #[test]
+    fn axis_should_stop_when_requested() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+
+        axis.run(Direction::Backward, 2000.0);
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        let steps_to_stop = axis.profile().steps_to_stop();
+        axis.stop();
+        let steps = positions(&mut axis).len() as u32;
+
+        assert!(steps <= steps_to_stop);
+        assert!(axis.is_idle());
+        assert_eq!(axis.position(), -300 - steps as i32);
+    }
+
+    #[test]
+    fn axis_should_track_jog_velocity_within_accel_limit() {
+        let target_accel = 6000.0;
+        let mut axis = Axis::new(Trapezoidal::new(target_accel));
+
+        let mut velocity_prev: Option<f32> = None;
+        for i in 0..2000 {
+            // Change the speed on every step, like an input device would.
+            let speed = if (i / 500) % 2 == 0 { 2000.0 } else { 500.0 };
+            axis.jog(speed + (i % 10) as f32);
+
+            let (direction, delay) = axis.next_step().unwrap();
+            assert_eq!(direction, Direction::Forward);
+
+            let velocity = 1.0 / delay;
+            assert!(velocity <= 2010.0);
+            if let Some(velocity_prev) = velocity_prev {
+                let accel = (velocity - velocity_prev) / delay;
+                assert!(accel.abs() <= target_accel * 1.1);
+            }
+            velocity_prev = Some(velocity);
+        }
+    }
+
+    #[test]
+    fn axis_should_stop_jogging_when_velocity_is_zero() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+
+        axis.jog(-1000.0);
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        let steps_to_stop = axis.profile().steps_to_stop();
+        let mut steps = 0;
+        loop {
+            axis.jog(0.0);
+            match axis.next_step() {
+                Some((direction, _)) => {
+                    assert_eq!(direction, Direction::Backward);
+                    steps += 1;
+                }
+                None => break,
+            }
+        }
+
+        assert!(steps <= steps_to_stop);
+        assert!(axis.is_idle());
+    }
+
+    #[test]
+    fn axis_should_reverse_jog_direction_after_stopping() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+
+        axis.jog(1000.0);
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        let mut directions = Vec::new();
+        for _ in 0..300 {
+            axis.jog(-1000.0);
+            let (direction, _) = axis.next_step().unwrap();
+            directions.push(direction);
+        }
+
+        let reversals = directions.windows(2).filter(|w| w[0] != w[1]).count();
+        assert_eq!(reversals, 1);
+        assert_eq!(directions.last(), Some(&Direction::Backward));
+    }
+
+    #[test]
+    fn axis_should_jog_unsigned_profile_with_signed_velocity() {
+        use fixed::types::{I32F32, U32F32};
+
+        let mut axis = Axis::new(Trapezoidal::new(U32F32::from_num(6000)));
+
+        for _ in 0..100 {
+            axis.jog(I32F32::from_num(-1000));
+            let (direction, _) = axis.next_step().unwrap();
+            assert_eq!(direction, Direction::Backward);
+        }
+        assert_eq!(axis.position(), -100);
+
+        axis.jog(I32F32::from_num(0));
+        while axis.next_step().is_some() {}
+        assert!(axis.is_idle());
+    }
+
+    #[test]
+    fn axis_should_stop_jogging_at_limit() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_limits(0, 400);
+
+        while axis.position() < 400 {
+            axis.jog(2000.0);
+            if axis.next_step().is_none() {
+                break;
+            }
+        }
+
+        axis.jog(2000.0);
+        assert!(axis.next_step().is_none());
+        assert_eq!(axis.position(), 400);
+    }
+
+    #[test]
+    fn axis_should_take_up_backlash_on_reversal() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_backlash(10, 200.0);
+
+        axis.move_to(100, 1000.0).unwrap();
+        assert_eq!(positions(&mut axis).len(), 100);
+
+        axis.move_to(0, 1000.0).unwrap();
+        for _ in 0..10 {
+            let (direction, delay) = axis.next_step().unwrap();
+            assert_eq!(direction, Direction::Backward);
+            assert_eq!(delay, 1.0 / 200.0);
+            assert_eq!(axis.position(), 100);
+        }
+        assert_eq!(positions(&mut axis).len(), 100);
+        assert_eq!(axis.position(), 0);
+
+        // No reversal, so no extra steps.
+        axis.move_to(-100, 1000.0).unwrap();
+        assert_eq!(positions(&mut axis).len(), 100);
+
+        axis.move_to(0, 1000.0).unwrap();
+        assert_eq!(positions(&mut axis).len(), 110);
+        assert_eq!(axis.position(), 0);
+    }
+
+    #[test]
+    fn axis_should_only_undo_partial_backlash_take_up() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_backlash(10, 200.0);
+
+        axis.move_to(100, 1000.0).unwrap();
+        positions(&mut axis);
+
+        axis.move_to(0, 1000.0).unwrap();
+        for _ in 0..4 {
+            axis.next_step();
+        }
+
+        axis.move_to(200, 1000.0).unwrap();
+        let mut steps = Vec::new();
+        while let Some((direction, _)) = axis.next_step() {
+            steps.push(direction);
+        }
+
+        assert_eq!(steps.len(), 4 + 100);
+        assert!(steps
+            .iter()
+            .all(|&direction| direction == Direction::Forward));
+        assert_eq!(axis.position(), 200);
+    }
+
+    #[test]
+    fn axis_should_make_up_for_lost_steps() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_encoder_thresholds(5, 100);
+
+        axis.move_to(1000, 1000.0).unwrap();
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        assert_eq!(axis.report_encoder(297), Correction::InTolerance);
+        assert_eq!(axis.position(), 300);
+
+        let correction = axis.report_encoder(280);
+        assert_eq!(correction, Correction::Corrected { error: -20 });
+        assert_eq!(axis.position(), 280);
+
+        assert_eq!(positions(&mut axis).len(), 720);
+        assert_eq!(axis.position(), 1000);
+    }
+
+    #[test]
+    fn axis_should_correct_position_after_motion_has_ended() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+
+        axis.move_to(1000, 1000.0).unwrap();
+        positions(&mut axis);
+
+        let correction = axis.report_encoder(1003);
+        assert_eq!(correction, Correction::Corrected { error: 3 });
+
+        let mut steps = Vec::new();
+        while let Some((direction, _)) = axis.next_step() {
+            steps.push(direction);
+        }
+        assert_eq!(steps, [Direction::Backward; 3]);
+        assert_eq!(axis.position(), 1000);
+    }
+
+    #[test]
+    fn axis_should_stop_on_stall() {
+        let mut axis = Axis::new(Trapezoidal::new(6000.0));
+        axis.set_encoder_thresholds(5, 100);
+
+        axis.move_to(1000, 1000.0).unwrap();
+        for _ in 0..300 {
+            axis.next_step();
+        }
+
+        let steps_to_stop = axis.profile().steps_to_stop();
+        let correction = axis.report_encoder(150);
+        assert_eq!(correction, Correction::Stall { error: -150 });
+
+        let steps = positions(&mut axis).len() as u32;
+        assert!(steps <= steps_to_stop);
+        assert_eq!(axis.position(), 150 + steps as i32);
+        assert!(axis.is_idle());
+    }
+}
@@ -0,0 +1,763 @@
+//! RampMaker CLI - Generate, inspect, and plot acceleration ramps
+//!
+//! Runs a motion profile with the parameters given on the command line, and
+//! prints either summary statistics, or the generated ramp in one of several
+//! formats. Run with `--help` for details.
+//!
+//! This binary is only available, if the **`cli`** feature is enabled.
+
+use std::{env, fmt::Write as _, io, process, str::FromStr};
+
+use ramp_maker::{
+    sim::{Accuracy, Phase, Simulation},
+    Austin, ExactTrapezoidal, Exponential, Flat, MotionProfile, Quintic,
+    Sinusoidal, Trapezoidal,
+};
+
+const USAGE: &str = "\
+Usage: ramp-maker-cli [OPTIONS]
+
+Options:
+    --profile <PROFILE>  Motion profile: flat, trapezoidal, exact-trapezoidal,
+                         austin, sinusoidal, exponential, quintic
+                         [default: trapezoidal]
+    --accel <ACCEL>      Target acceleration in steps/t^2 [default: 1000]
+    --time-constant <T>  Time constant of the exponential profile in t
+                         [default: 0.05]
+    --velocity <VEL>     Maximum velocity in steps/t [default: 1000]
+    --steps <STEPS>      Number of steps [default: 1000]
+    --num <NUM>          Numeric type: f32, f64, fixed (64-bit fixed-point
+                         number with 32 fractional bits) [default: f64]
+    --format <FORMAT>    Output: summary, accuracy, csv, json, c, rust, svg
+                         [default: summary]
+    --scale <SCALE>      Factor applied to delays in c/rust arrays, before
+                         rounding them to integers [default: 1]
+    --help               Print this message
+
+The `accuracy` output compares the selected numeric type to `f64`, to help
+with choosing the smallest numeric type that is safe for the parameters.
+
+The unit of time t is the unit of the delays. Use steps per timer count for
+the parameters to get delays in timer counts.
+";
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return;
+        }
+        Err(err) => {
+            eprintln!("Error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if args.format == Format::Accuracy {
+        match accuracy(&args) {
+            Ok(accuracy) => print!("{}", accuracy_report(&accuracy)),
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let simulation = match simulate(&args) {
+        Ok(simulation) => simulation,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let array = match args.format {
+        Format::C => Some(c_array(&simulation, args.scale)),
+        Format::Rust => Some(rust_array(&simulation, args.scale)),
+        _ => None,
+    };
+    if let Some(Err(err)) = array {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+
+    let stdout = io::stdout();
+    let stdout = stdout.lock();
+
+    let result = match args.format {
+        Format::Summary => {
+            print!("{}", summary(&args, &simulation));
+            Ok(())
+        }
+        Format::Accuracy => unreachable!("Handled above"),
+        Format::Csv => simulation.write_csv(stdout),
+        Format::Json => simulation.write_json(stdout),
+        Format::C | Format::Rust => {
+            if let Some(Ok(array)) = array {
+                print!("{}", array);
+            }
+            Ok(())
+        }
+        Format::Svg => {
+            print!("{}", svg(&simulation));
+            Ok(())
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error writing output: {}", err);
+        process::exit(1);
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    profile: Profile,
+    accel: f64,
+    time_constant: f64,
+    velocity: f64,
+    steps: u32,
+    num: Num,
+    format: Format,
+    scale: f64,
+}
+
+impl Args {
+    /// Parse the command-line arguments
+    ///
+    /// Returns `Ok(None)`, if the user asked for help.
+    fn parse(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<Option<Self>, String> {
+        let mut parsed = Self {
+            profile: Profile::Trapezoidal,
+            accel: 1000.0,
+            time_constant: 0.05,
+            velocity: 1000.0,
+            steps: 1000,
+            num: Num::F64,
+            format: Format::Summary,
+            scale: 1.0,
+        };
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Ok(None);
+            }
+
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for `{}`", arg))?;
+
+            match arg.as_str() {
+                "--profile" => parsed.profile = parse(&arg, &value)?,
+                "--accel" => parsed.accel = parse(&arg, &value)?,
+                "--time-constant" => {
+                    parsed.time_constant = parse(&arg, &value)?
+                }
+                "--velocity" => parsed.velocity = parse(&arg, &value)?,
+                "--steps" => parsed.steps = parse(&arg, &value)?,
+                "--num" => parsed.num = parse(&arg, &value)?,
+                "--format" => parsed.format = parse(&arg, &value)?,
+                "--scale" => parsed.scale = parse(&arg, &value)?,
+                _ => return Err(format!("Unknown argument `{}`", arg)),
+            }
+        }
+
+        if !parsed.accel.is_finite() || parsed.accel <= 0.0 {
+            return Err("Acceleration must be positive and finite".into());
+        }
+        if !parsed.time_constant.is_finite() || parsed.time_constant <= 0.0 {
+            return Err("Time constant must be positive and finite".into());
+        }
+        if !parsed.velocity.is_finite() || parsed.velocity <= 0.0 {
+            return Err("Velocity must be positive and finite".into());
+        }
+
+        Ok(Some(parsed))
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value `{}` for `{}`", value, arg))
+}
+
+macro_rules! keyword_enum {
+    ($name:ident { $($variant:ident => $keyword:literal,)* }) => {
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        enum $name {
+            $($variant,)*
+        }
+
+        impl FromStr for $name {
+            type Err = ();
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($keyword => Ok(Self::$variant),)*
+                    _ => Err(()),
+                }
+            }
+        }
+    };
+}
+
+keyword_enum!(Profile {
+    Flat => "flat",
+    Trapezoidal => "trapezoidal",
+    ExactTrapezoidal => "exact-trapezoidal",
+    Austin => "austin",
+    Sinusoidal => "sinusoidal",
+    Exponential => "exponential",
+    Quintic => "quintic",
+});
+
+impl Profile {
+    /// Indicate whether the profile is meant to accelerate at a constant rate
+    fn has_constant_accel(self) -> bool {
+        match self {
+            Self::Trapezoidal | Self::ExactTrapezoidal | Self::Austin => true,
+            Self::Flat
+            | Self::Sinusoidal
+            | Self::Exponential
+            | Self::Quintic => false,
+        }
+    }
+}
+
+keyword_enum!(Num {
+    F32 => "f32",
+    F64 => "f64",
+    Fixed => "fixed",
+});
+
+keyword_enum!(Format {
+    Summary => "summary",
+    Accuracy => "accuracy",
+    Csv => "csv",
+    Json => "json",
+    C => "c",
+    Rust => "rust",
+    Svg => "svg",
+});
+
+fn simulate(args: &Args) -> Result<Simulation, String> {
+    match args.num {
+        Num::F32 => simulate_with::<f32>(args),
+        Num::F64 => simulate_with::<f64>(args),
+        Num::Fixed => simulate_with::<Fixed>(args),
+    }
+}
+
+fn simulate_with<N>(args: &Args) -> Result<Simulation, String>
+where
+    N: CliNum + az::Cast<f64>,
+    AnyProfile<N>: MotionProfile<Velocity = N, Delay = N>,
+    f64: az::CheckedCast<N>,
+{
+    let mut profile = N::profile(args)?;
+    profile.enter_position_mode(convert(args.velocity)?, args.steps);
+
+    Ok(Simulation::run(&mut profile))
+}
+
+fn accuracy(args: &Args) -> Result<Accuracy, String> {
+    match args.num {
+        Num::F32 => accuracy_with::<f32>(args),
+        Num::F64 => accuracy_with::<f64>(args),
+        Num::Fixed => accuracy_with::<Fixed>(args),
+    }
+}
+
+fn accuracy_with<N>(args: &Args) -> Result<Accuracy, String>
+where
+    N: CliNum + Copy + PartialEq + num_traits::Bounded + az::Cast<f64>,
+    AnyProfile<N>: MotionProfile<Velocity = N, Delay = N>,
+    f64: az::CheckedCast<N>,
+{
+    let mut reference = f64::profile(args)?;
+    reference.enter_position_mode(args.velocity, args.steps);
+
+    let mut profile = N::profile(args)?;
+    profile.enter_position_mode(convert(args.velocity)?, args.steps);
+
+    Ok(Accuracy::compare(&mut reference, &mut profile))
+}
+
+/// The fixed-point type selected by `--num fixed`
+type Fixed = fixed::types::U32F32;
+
+/// A numeric type that can be selected on the command line
+trait CliNum: Sized {
+    /// Create the motion profile selected on the command line
+    fn profile(args: &Args) -> Result<AnyProfile<Self>, String>;
+}
+
+macro_rules! impl_cli_num {
+    ($($num:ty,)*) => {
+        $(
+            impl CliNum for $num {
+                fn profile(args: &Args) -> Result<AnyProfile<Self>, String> {
+                    let accel = args.accel;
+                    let profile = match args.profile {
+                        Profile::Flat => AnyProfile::Flat(Flat::new()),
+                        Profile::Trapezoidal => AnyProfile::Trapezoidal(
+                            Trapezoidal::new(convert(accel)?),
+                        ),
+                        Profile::ExactTrapezoidal => {
+                            AnyProfile::ExactTrapezoidal(
+                                ExactTrapezoidal::new(convert(accel)?),
+                            )
+                        }
+                        Profile::Austin => {
+                            AnyProfile::Austin(Austin::new(convert(accel)?))
+                        }
+                        Profile::Sinusoidal => AnyProfile::Sinusoidal(
+                            Sinusoidal::new(convert(accel)?),
+                        ),
+                        Profile::Exponential => AnyProfile::Exponential(
+                            Exponential::new(convert(args.time_constant)?),
+                        ),
+                        Profile::Quintic => {
+                            AnyProfile::Quintic(Quintic::new(convert(accel)?))
+                        }
+                    };
+
+                    Ok(profile)
+                }
+            }
+        )*
+    };
+}
+
+impl_cli_num!(f32, f64, Fixed,);
+
+/// Any of the motion profiles that can be selected on the command line
+enum AnyProfile<Num> {
+    Flat(Flat<Num>),
+    Trapezoidal(Trapezoidal<Num>),
+    ExactTrapezoidal(ExactTrapezoidal<Num>),
+    Austin(Austin<Num>),
+    Sinusoidal(Sinusoidal<Num>),
+    Exponential(Exponential<Num>),
+    Quintic(Quintic<Num>),
+}
+
+/// Forward a method call to the motion profile wrapped by [`AnyProfile`]
+macro_rules! delegate {
+    ($profile:expr, $p:ident => $call:expr) => {
+        match $profile {
+            AnyProfile::Flat($p) => $call,
+            AnyProfile::Trapezoidal($p) => $call,
+            AnyProfile::ExactTrapezoidal($p) => $call,
+            AnyProfile::Austin($p) => $call,
+            AnyProfile::Sinusoidal($p) => $call,
+            AnyProfile::Exponential($p) => $call,
+            AnyProfile::Quintic($p) => $call,
+        }
+    };
+}
+
+impl<Num> MotionProfile for AnyProfile<Num>
+where
+    Flat<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+    Trapezoidal<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+    ExactTrapezoidal<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+    Austin<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+    Sinusoidal<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+    Exponential<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+    Quintic<Num>: MotionProfile<Velocity = Num, Delay = Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(&mut self, max_velocity: Num, num_steps: u32) {
+        delegate!(self, p => p.enter_position_mode(max_velocity, num_steps))
+    }
+
+    fn next_delay(&mut self) -> Option<Num> {
+        delegate!(self, p => p.next_delay())
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        delegate!(self, p => p.steps_remaining())
+    }
+
+    fn current_velocity(&self) -> Num {
+        delegate!(self, p => p.current_velocity())
+    }
+
+    fn is_idle(&self) -> bool {
+        delegate!(self, p => p.is_idle())
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        delegate!(self, p => p.steps_to_stop())
+    }
+}
+
+fn convert<Num>(value: f64) -> Result<Num, String>
+where
+    f64: az::CheckedCast<Num>,
+{
+    az::checked_cast(value).ok_or_else(|| {
+        format!("Value {} can't be represented by numeric type", value)
+    })
+}
+
+fn summary(args: &Args, simulation: &Simulation) -> String {
+    let mut summary = String::new();
+
+    let _ = writeln!(summary, "Steps:            {}", simulation.rows.len());
+    let _ = writeln!(summary, "Duration:         {}", simulation.duration());
+    let _ =
+        writeln!(summary, "Peak velocity:    {}", simulation.peak_velocity());
+
+    if args.profile.has_constant_accel() {
+        let (ramp, ramp_ends) = accel_errors(args, simulation);
+
+        let _ = writeln!(summary, "Max. accel error: {:.2}%", ramp * 100.0);
+        let _ = writeln!(
+            summary,
+            "  at ramp ends:   {:.2}% (first/last {} steps of each ramp)",
+            ramp_ends * 100.0,
+            RAMP_END_STEPS,
+        );
+    }
+
+    summary
+}
+
+/// The number of steps at either end of a ramp that are reported separately
+///
+/// The recurrence that the constant-acceleration profiles use to compute the
+/// delays is an approximation, which is inaccurate for the first few steps
+/// after a change in acceleration. Its error depends only on the number of
+/// steps since that change, not on the parameters of the motion, and drops
+/// below 3% after this many steps.
+const RAMP_END_STEPS: usize = 5;
+
+/// Return the maximum relative acceleration error within the ramps, and at
+/// their ends
+fn accel_errors(args: &Args, simulation: &Simulation) -> (f64, f64) {
+    let mut ramp = 0.0;
+    let mut ramp_ends = 0.0;
+
+    // Only steps that are part of a ramp are relevant here. The acceleration
+    // is expected to be zero otherwise.
+    let mut rows = simulation.rows.iter().peekable();
+    while let Some(row) = rows.next() {
+        let phase = match row.phase {
+            Some(phase @ (Phase::Accelerating | Phase::Decelerating)) => phase,
+            _ => continue,
+        };
+
+        let mut errors = vec![row.acceleration];
+        while let Some(row) = rows.next_if(|row| row.phase == Some(phase)) {
+            errors.push(row.acceleration);
+        }
+
+        for (i, accel) in errors.iter().enumerate() {
+            let accel = accel.unwrap_or(0.0);
+            let error = (accel.abs() - args.accel).abs() / args.accel;
+
+            let at_end =
+                i < RAMP_END_STEPS || i + RAMP_END_STEPS >= errors.len();
+            if at_end {
+                ramp_ends = f64::max(ramp_ends, error);
+            } else {
+                ramp = f64::max(ramp, error);
+            }
+        }
+    }
+
+    (ramp, ramp_ends)
+}
+
+fn accuracy_report(accuracy: &Accuracy) -> String {
+    let mut report = String::new();
+
+    let _ = writeln!(
+        report,
+        "Max. delay error: {:.4}%",
+        accuracy.max_delay_error() * 100.0
+    );
+    let _ = writeln!(report, "Time error:       {}", accuracy.time_error);
+    let _ = writeln!(report, "Saturated:        {}", accuracy.saturated);
+    let _ = writeln!(report, "Outcome:          {}", accuracy.outcome);
+
+    report
+}
+
+fn c_array(simulation: &Simulation, scale: f64) -> Result<String, String> {
+    let mut array = String::new();
+
+    let _ = writeln!(
+        array,
+        "const uint32_t DELAYS[{}] = {{",
+        simulation.rows.len()
+    );
+    write_delays(&mut array, simulation, scale)?;
+    let _ = writeln!(array, "}};");
+
+    Ok(array)
+}
+
+fn rust_array(simulation: &Simulation, scale: f64) -> Result<String, String> {
+    let mut array = String::new();
+
+    let _ = writeln!(
+        array,
+        "pub const DELAYS: [u32; {}] = [",
+        simulation.rows.len()
+    );
+    write_delays(&mut array, simulation, scale)?;
+    let _ = writeln!(array, "];");
+
+    Ok(array)
+}
+
+fn write_delays(
+    out: &mut String,
+    simulation: &Simulation,
+    scale: f64,
+) -> Result<(), String> {
+    for chunk in simulation.rows.chunks(8) {
+        let _ = write!(out, "   ");
+        for row in chunk {
+            let delay = (row.delay * scale).round();
+            let delay =
+                az::checked_cast::<f64, u32>(delay).ok_or_else(|| {
+                    format!(
+                    "Delay {} of step {} doesn't fit into `u32` at scale {}",
+                    row.delay, row.step, scale
+                )
+                })?;
+            let _ = write!(out, " {},", delay);
+        }
+        let _ = writeln!(out);
+    }
+
+    Ok(())
+}
+
+fn svg(simulation: &Simulation) -> String {
+    const WIDTH: f64 = 800.0;
+    const PLOT_HEIGHT: f64 = 250.0;
+    const MARGIN: f64 = 50.0;
+
+    let duration = simulation.duration();
+    let x = |time: f64| {
+        let scale = if duration > 0.0 { duration } else { 1.0 };
+        MARGIN + time / scale * (WIDTH - 2.0 * MARGIN)
+    };
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">",
+        WIDTH,
+        2.0 * PLOT_HEIGHT + 3.0 * MARGIN,
+    );
+    let _ =
+        writeln!(svg, "<rect width=\"100%\" height=\"100%\" fill=\"white\"/>");
+
+    let velocities: Vec<_> = simulation
+        .rows
+        .iter()
+        .map(|row| (row.time, row.velocity))
+        .collect();
+    let accelerations: Vec<_> = simulation
+        .rows
+        .iter()
+        .filter_map(|row| row.acceleration.map(|accel| (row.time, accel)))
+        .collect();
+
+    let plots = [("velocity", &velocities), ("acceleration", &accelerations)];
+    for (i, (label, points)) in plots.iter().enumerate() {
+        let top = MARGIN + i as f64 * (PLOT_HEIGHT + MARGIN);
+
+        let max = points.iter().map(|(_, v)| v.abs()).fold(0.0, f64::max);
+        let max = if max > 0.0 { max } else { 1.0 };
+
+        // Velocity is never negative, so it gets the full height. Acceleration
+        // is centered around zero.
+        let (zero, scale) = if i == 0 {
+            (top + PLOT_HEIGHT, PLOT_HEIGHT / max)
+        } else {
+            (top + PLOT_HEIGHT / 2.0, PLOT_HEIGHT / 2.0 / max)
+        };
+        let y = |value: f64| zero - value * scale;
+
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" \
+            font-size=\"14\">{} (max: {})</text>",
+            MARGIN,
+            top - 10.0,
+            label,
+            max,
+        );
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"gray\"/>",
+            MARGIN,
+            y(0.0),
+            WIDTH - MARGIN,
+            y(0.0),
+        );
+        let _ = writeln!(
+            svg,
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"gray\"/>",
+            MARGIN,
+            top,
+            MARGIN,
+            top + PLOT_HEIGHT,
+        );
+
+        let _ =
+            write!(svg, "<polyline fill=\"none\" stroke=\"blue\" points=\"");
+        for (time, value) in points.iter() {
+            let _ = write!(svg, "{:.2},{:.2} ", x(*time), y(*value));
+        }
+        let _ = writeln!(svg, "\"/>");
+    }
+
+    let _ = writeln!(svg, "</svg>");
+
+    svg
+}
+
+#[cfg(test)]
+mod tests {
+    use ramp_maker::sim::Outcome;
+
+    use super::{
+        accel_errors, accuracy, c_array, rust_array, simulate, svg, Args,
+        Format, Num,
+    };
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn cli_should_parse_arguments() {
+        let args = args(&[
+            "--profile",
+            "flat",
+            "--velocity",
+            "500",
+            "--num",
+            "fixed",
+            "--format",
+            "csv",
+        ]);
+
+        assert_eq!(args.velocity, 500.0);
+        assert_eq!(args.num, Num::Fixed);
+        assert_eq!(args.format, Format::Csv);
+
+        assert_eq!(
+            Args::parse(vec!["--help".to_string()].into_iter()),
+            Ok(None)
+        );
+        assert!(Args::parse(vec!["--steps".to_string()].into_iter()).is_err());
+        assert!(
+            Args::parse(["--num", "u8"].iter().map(|s| s.to_string())).is_err()
+        );
+    }
+
+    #[test]
+    fn cli_should_reject_invalid_parameters() {
+        for &(arg, value) in &[
+            ("--accel", "0"),
+            ("--accel", "-5"),
+            ("--accel", "nan"),
+            ("--accel", "inf"),
+            ("--velocity", "0"),
+            ("--velocity", "-5"),
+            ("--velocity", "nan"),
+            ("--velocity", "inf"),
+            ("--time-constant", "inf"),
+        ] {
+            let args = vec![arg.to_string(), value.to_string()];
+            let result = Args::parse(args.into_iter());
+            assert!(result.is_err(), "{} {}", arg, value);
+        }
+    }
+
+    #[test]
+    fn cli_should_report_accel_error_at_ramp_ends_separately() {
+        for velocity in &["1000", "500"] {
+            let args = args(&["--velocity", velocity]);
+            let simulation = simulate(&args).unwrap();
+
+            let (ramp, ramp_ends) = accel_errors(&args, &simulation);
+            assert!(ramp < 0.03);
+            assert!(ramp_ends > ramp);
+        }
+    }
+
+    #[test]
+    fn cli_should_simulate_all_numeric_types() {
+        for num in &["f32", "f64", "fixed"] {
+            for profile in &[
+                "flat",
+                "trapezoidal",
+                "exact-trapezoidal",
+                "austin",
+                "sinusoidal",
+                "exponential",
+                "quintic",
+            ] {
+                let args = args(&[
+                    "--profile",
+                    profile,
+                    "--num",
+                    num,
+                    "--steps",
+                    "200",
+                ]);
+                let simulation = simulate(&args).unwrap();
+                assert_eq!(simulation.rows.len(), 200);
+            }
+        }
+    }
+
+    #[test]
+    fn cli_should_export_arrays_and_plots() {
+        let args = args(&["--steps", "20", "--scale", "1000000"]);
+        let simulation = simulate(&args).unwrap();
+
+        let c = c_array(&simulation, args.scale).unwrap();
+        assert!(c.starts_with("const uint32_t DELAYS[20] = {"));
+        assert_eq!(c.matches(',').count(), 20);
+
+        let rust = rust_array(&simulation, args.scale).unwrap();
+        assert!(rust.starts_with("pub const DELAYS: [u32; 20] = ["));
+
+        // Delays that don't fit into the array's type are an error.
+        assert!(c_array(&simulation, 1e15).is_err());
+        assert!(rust_array(&simulation, -1e6).is_err());
+
+        let svg = svg(&simulation);
+        assert!(svg.starts_with("<svg"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+
+    #[test]
+    fn cli_should_compare_accuracy() {
+        let args = args(&["--num", "fixed", "--format", "accuracy"]);
+        let accuracy = accuracy(&args).unwrap();
+
+        assert_eq!(accuracy.outcome, Outcome::Complete);
+        assert!(accuracy.is_within(0.05));
+    }
+}
@@ -0,0 +1,436 @@
+//! Exact trapezoidal motion profile
+//!
+//! See [`ExactTrapezoidal`].
+
+use core::ops;
+
+use az::Az as _;
+
+use crate::{util::traits::Sqrt, MotionProfile};
+
+/// Exact trapezoidal motion profile
+///
+/// Generates a trapezoidal ramp, computing the time of each step from the
+/// closed-form kinematic equations of constant acceleration. During
+/// acceleration, the `n`th step is made at time `t_n = sqrt(2n / a)`, and the
+/// delays are the differences between consecutive step times. Deceleration
+/// mirrors acceleration.
+///
+/// Compared to [`Trapezoidal`], which uses an approximation that has some
+/// inaccuracies, especially at the beginning and end of the ramp, this profile
+/// produces exact step times and positions. This comes at the cost of computing
+/// one square root per step, so [`Trapezoidal`] is still the better choice,
+/// if speed is more important than accuracy.
+///
+/// Create an instance of this struct using [`ExactTrapezoidal::new`], then use
+/// the API defined by [`MotionProfile`] (which this struct implements) to
+/// generate the acceleration ramp.
+///
+/// # Acceleration Ramp
+///
+/// This struct will generate a trapezoidal acceleration ramp with the following
+/// attributes:
+/// - The velocity will always be equal to or less than the maximum velocity
+///   passed to [`MotionProfile::enter_position_mode`].
+/// - While ramping up or down, each step is made at exactly the time dictated
+///   by the target acceleration.
+///
+/// # Unit of Time
+///
+/// This code is agnostic on which unit of time is used. If you provide the
+/// target acceleration and maximum velocity in steps per second, the unit of
+/// the delay returned will be seconds.
+///
+/// This allows you to pass the parameters in steps per number of timer counts
+/// for the timer you're using, completely eliminating any conversion overhead
+/// for the delay.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent the
+/// target acceleration, maximum velocity, and delays per step. It is set to a
+/// 64-bit fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other type from the
+/// `fixed` crate. Please note that you need to enable support for `f32`/`f64`
+/// explicitly. Check out the section on Cargo features from the documentation
+/// in the root module for more information.
+///
+/// [`Trapezoidal`]: crate::Trapezoidal
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExactTrapezoidal<Num = DefaultNum> {
+    delay_min: Option<Num>,
+    delay_prev: Num,
+
+    /// The time it takes to make the first step from stand-still
+    ///
+    /// The time of the `n`th step on the ramp is this factor, multiplied by
+    /// the square root of `n`.
+    time_factor: Num,
+
+    /// The number of steps on the ramp, i.e. the number of steps it would take
+    /// to reach the current velocity from stand-still
+    ramp_steps: u32,
+
+    /// The square roots of `ramp_steps` and `ramp_steps - 1`
+    ///
+    /// Cached, so only one square root needs to be computed per step.
+    ramp_steps_sqrt: Num,
+    ramp_steps_prev_sqrt: Num,
+
+    steps_left: u32,
+
+    /// Whether a step has been made since the motion profile was last idle
+    moving: bool,
+}
+
+impl<Num> ExactTrapezoidal<Num>
+where
+    Num: Copy
+        + num_traits::Zero
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Div<Output = Num>
+        + Sqrt,
+{
+    /// Create a new instance of `ExactTrapezoidal`
+    ///
+    /// Accepts the target acceleration in steps per (unit of time)^2 as an
+    /// argument. It must not be zero. See the struct documentation for
+    /// information about units of time.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `target_accel` is zero.
+    pub fn new(target_accel: Num) -> Self {
+        let two = Num::one() + Num::one();
+        let time_factor = (two / target_accel).sqrt();
+
+        Self {
+            delay_min: None,
+            delay_prev: time_factor,
+
+            time_factor,
+
+            ramp_steps: 0,
+            ramp_steps_sqrt: Num::zero(),
+            ramp_steps_prev_sqrt: Num::zero(),
+
+            steps_left: 0,
+            moving: false,
+        }
+    }
+}
+
+// Needed for the `MotionProfile` test suite in `crate::util::testing`.
+#[cfg(test)]
+impl Default for ExactTrapezoidal<f32> {
+    fn default() -> Self {
+        Self::new(6000.0)
+    }
+}
+
+impl<Num> MotionProfile for ExactTrapezoidal<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::Inv<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + Sqrt,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        if self.is_idle() {
+            self.moving = false;
+        }
+
+        self.delay_min = if max_velocity.is_zero() {
+            None
+        } else {
+            Some(max_velocity.inv())
+        };
+
+        self.steps_left = num_steps;
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        let delay_next = match RampMode::compute(self) {
+            RampMode::Idle => {
+                return None;
+            }
+            RampMode::RampUp {
+                ramp_steps_sqrt_next,
+                delay_next,
+            } => {
+                self.ramp_steps += 1;
+                self.ramp_steps_prev_sqrt = self.ramp_steps_sqrt;
+                self.ramp_steps_sqrt = ramp_steps_sqrt_next;
+                delay_next
+            }
+            RampMode::Plateau { delay_next } => delay_next,
+            RampMode::RampDown { delay_next } => {
+                self.ramp_steps -= 1;
+                self.ramp_steps_sqrt = self.ramp_steps_prev_sqrt;
+                self.ramp_steps_prev_sqrt = match self.ramp_steps {
+                    0 => Num::zero(),
+                    n => (n - 1).az::<Num>().sqrt(),
+                };
+                delay_next
+            }
+        };
+
+        self.delay_prev = delay_next;
+        self.steps_left = self.steps_left.saturating_sub(1);
+        self.moving = true;
+
+        Some(delay_next)
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.steps_left
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        if !self.moving || self.is_idle() {
+            return Num::zero();
+        }
+
+        self.delay_prev.inv()
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(RampMode::compute(self), RampMode::Idle)
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        // Ramping down takes as many steps as there are on the ramp.
+        self.ramp_steps
+    }
+}
+
+/// The default numeric type used by [`ExactTrapezoidal`]
+pub type DefaultNum = fixed::FixedU64<typenum::U32>;
+
+enum RampMode<Num> {
+    Idle,
+    RampUp {
+        ramp_steps_sqrt_next: Num,
+        delay_next: Num,
+    },
+    Plateau {
+        delay_next: Num,
+    },
+    RampDown {
+        delay_next: Num,
+    },
+}
+
+impl<Num> RampMode<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + Sqrt,
+    u32: az::Cast<Num>,
+{
+    fn compute(profile: &ExactTrapezoidal<Num>) -> Self {
+        let steps_left = profile.steps_left;
+        let ramp_steps = profile.ramp_steps;
+
+        if steps_left == 0 && ramp_steps == 0 {
+            return Self::Idle;
+        }
+
+        // The delay of the last step on the ramp. This is the delay we return
+        // when ramping down.
+        let ramp_delay = profile.time_factor
+            * (profile.ramp_steps_sqrt - profile.ramp_steps_prev_sqrt);
+        let ramp_down = Self::RampDown {
+            delay_next: ramp_delay,
+        };
+
+        // Ramping down takes exactly as many steps as there are on the ramp.
+        // If the target step is that close, we need to decelerate.
+        if steps_left <= ramp_steps {
+            return ramp_down;
+        }
+
+        let delay_min = match profile.delay_min {
+            Some(delay_min) => delay_min,
+            None => {
+                // No minimum delay means someone set max velocity to zero.
+                return if ramp_steps == 0 {
+                    Self::Idle
+                } else {
+                    ramp_down
+                };
+            }
+        };
+
+        // If the last step on the ramp is too fast, someone lowered the
+        // maximum velocity.
+        if ramp_steps > 0 && ramp_delay < delay_min {
+            return ramp_down;
+        }
+
+        // Accelerating adds a step to the ramp, which then needs an additional
+        // step to ramp down. If that doesn't fit, we need to keep the current
+        // velocity for a step.
+        if steps_left == ramp_steps + 1 {
+            return Self::Plateau {
+                delay_next: profile.delay_prev,
+            };
+        }
+
+        let ramp_steps_sqrt_next = (ramp_steps + 1).az::<Num>().sqrt();
+        let delay_next = profile.time_factor
+            * (ramp_steps_sqrt_next - profile.ramp_steps_sqrt);
+
+        if delay_next < delay_min {
+            return Self::Plateau {
+                delay_next: delay_min,
+            };
+        }
+
+        Self::RampUp {
+            ramp_steps_sqrt_next,
+            delay_next,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{ExactTrapezoidal, MotionProfile as _};
+
+    #[test]
+    fn exact_trapezoidal_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<ExactTrapezoidal<f32>>();
+    }
+
+    #[test]
+    fn exact_trapezoidal_should_produce_exact_step_times() {
+        let target_accel = 6000.0;
+        let mut profile = ExactTrapezoidal::<f64>::new(target_accel);
+
+        let max_velocity = 1000.0;
+        let num_steps = 200;
+        profile.enter_position_mode(max_velocity, num_steps);
+
+        let delays: Vec<f64> = profile.delays().collect();
+        assert_eq!(delays.len() as u32, num_steps);
+
+        // While ramping up, the `n`th step must be made at `sqrt(2n / a)`.
+        let mut time = 0.0;
+        for (i, delay) in delays.iter().enumerate() {
+            time += delay;
+
+            let n = i as f64 + 1.0;
+            let velocity = target_accel * time;
+            if velocity >= max_velocity {
+                break;
+            }
+
+            assert_abs_diff_eq!(
+                time,
+                (2.0 * n / target_accel).sqrt(),
+                epsilon = 1e-12,
+            );
+        }
+
+        // The ramp must be symmetric.
+        let ramp_up = delays.iter().take_while(|&&delay| delay > 0.001);
+        let ramp_down = delays.iter().rev().take_while(|&&delay| delay > 0.001);
+        assert!(ramp_up.eq(ramp_down));
+
+        // The total duration must match the closed-form solution for a
+        // trapezoidal motion. The plateau doesn't start at an exact step, so
+        // allow for some error.
+        let duration: f64 = delays.iter().sum();
+        let expected =
+            num_steps as f64 / max_velocity + max_velocity / target_accel;
+        assert_abs_diff_eq!(duration, expected, epsilon = 0.001);
+    }
+
+    #[test]
+    fn exact_trapezoidal_should_come_to_stop_on_target_step() {
+        for num_steps in 1..50 {
+            let mut profile = ExactTrapezoidal::<f64>::new(6000.0);
+            profile.enter_position_mode(1000.0, num_steps);
+
+            let delays: Vec<f64> = profile.delays().collect();
+            assert_eq!(delays.len() as u32, num_steps);
+
+            // Last step must be made at the lowest velocity.
+            assert_abs_diff_eq!(
+                *delays.last().unwrap(),
+                (2.0 / 6000.0f64).sqrt(),
+                epsilon = 1e-12,
+            );
+        }
+    }
+
+    #[test]
+    fn exact_trapezoidal_should_adapt_to_changes_in_max_velocity() {
+        let mut profile = ExactTrapezoidal::<f64>::new(6000.0);
+
+        // Accelerate to maximum velocity.
+        profile.enter_position_mode(1000.0, 10_000);
+        let mut prev_delay = profile.next_delay().unwrap();
+        loop {
+            let delay = profile.next_delay().unwrap();
+            if delay == prev_delay {
+                break;
+            }
+            assert!(delay < prev_delay);
+            prev_delay = delay;
+        }
+        assert!(1.0 / prev_delay <= 1000.0);
+        assert!(1.0 / prev_delay > 950.0);
+
+        // Decelerate to a lower maximum velocity.
+        profile.enter_position_mode(500.0, 10_000);
+        loop {
+            let delay = profile.next_delay().unwrap();
+            if delay == prev_delay {
+                break;
+            }
+            assert!(delay > prev_delay);
+            prev_delay = delay;
+        }
+        assert!(1.0 / prev_delay <= 500.0);
+        assert!(1.0 / prev_delay > 450.0);
+
+        // Decelerate to stand-still.
+        profile.enter_position_mode(0.0, 10_000);
+        for delay in profile.delays() {
+            assert!(delay > prev_delay);
+            prev_delay = delay;
+        }
+    }
+
+    #[test]
+    fn exact_trapezoidal_should_work_with_default_num() {
+        use crate::exact_trapezoidal::DefaultNum;
+
+        let mut profile =
+            ExactTrapezoidal::<DefaultNum>::new(DefaultNum::from_num(6000));
+        profile.enter_position_mode(DefaultNum::from_num(1000), 200);
+
+        assert_eq!(profile.delays().count(), 200);
+    }
+}
@@ -0,0 +1,316 @@
+//! Exponential motion profile
+//!
+//! See [`Exponential`].
+
+use core::ops;
+
+use az::Az as _;
+
+use crate::{
+    util::{
+        ramp::{Motion, Shape},
+        sub,
+        traits::{Ceil, Exp, Ln},
+    },
+    MotionProfile,
+};
+
+/// Exponential motion profile
+///
+/// Generates a ramp whose velocity approaches the maximum velocity
+/// exponentially, like the voltage of a charging RC circuit:
+/// `v(t) = v_max * (1 - e^(-t / τ))`, where `τ` is the time constant. This
+/// accelerates hard at low velocities, where stepper motors have the most
+/// torque, and gently at high velocities, where they have the least. Many
+/// legacy stepper controllers use this kind of ramp.
+///
+/// As the velocity only approaches its limit asymptotically, the ramp ends
+/// once 99% of it have been reached, which happens after `τ * ln(100)`
+/// (roughly 4.6 time constants). The limit is chosen slightly above the
+/// maximum velocity, so the ramp ends exactly at the maximum velocity, and the
+/// motion continues there without a jump in velocity. The acceleration drops
+/// from 1% of its initial value to zero at that point.
+///
+/// The time of each step is computed by solving for the position of the step
+/// using Newton's method, which requires a few evaluations of the exponential
+/// function per step.
+///
+/// Create an instance of this struct using [`Exponential::new`], then use the
+/// API defined by [`MotionProfile`] (which this struct implements) to generate
+/// the acceleration ramp.
+///
+/// # Acceleration Ramp
+///
+/// This struct will generate a ramp with the following attributes:
+/// - The velocity will always be equal to or less than the maximum velocity
+///   passed to [`MotionProfile::enter_position_mode`].
+/// - Deceleration mirrors acceleration.
+/// - If the motion is too short to complete the ramp, the ramp down starts
+///   halfway through the motion.
+///
+/// Calling [`MotionProfile::enter_position_mode`] while a motion is in
+/// progress re-targets that motion, relative to the current position. If the
+/// new target is too close to stop on it, the motion comes to a stop as soon
+/// as possible instead, overshooting the target. The velocity of a motion in
+/// progress doesn't change, so the new maximum velocity only applies to motions
+/// that start from a stand-still.
+///
+/// # Unit of Time
+///
+/// This code is agnostic on which unit of time is used. If you provide the
+/// time constant in seconds and the maximum velocity in steps per second, the
+/// unit of the delay returned will be seconds.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent the
+/// time constant, maximum velocity, and delays per step. It is set to a 64-bit
+/// fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other type from the
+/// `fixed` crate. Please note that you need to enable support for `f32`/`f64`
+/// explicitly. Check out the section on Cargo features from the documentation
+/// in the root module for more information.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Exponential<Num = DefaultNum> {
+    time_constant: Num,
+    motion: Option<Motion<Num>>,
+}
+
+impl<Num> Exponential<Num> {
+    /// Create a new instance of `Exponential`
+    ///
+    /// Accepts the time constant of the ramp as an argument. It must not be
+    /// zero. See the struct documentation for information about units of time.
+    pub fn new(time_constant: Num) -> Self {
+        Self {
+            time_constant,
+            motion: None,
+        }
+    }
+}
+
+// Needed for the `MotionProfile` test suite in `crate::util::testing`.
+#[cfg(test)]
+impl Default for Exponential<f32> {
+    fn default() -> Self {
+        Self::new(0.05)
+    }
+}
+
+impl<Num> MotionProfile for Exponential<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + Exp
+        + Ln
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        if let Some(motion) = &mut self.motion {
+            if motion.is_moving() {
+                let num_steps =
+                    if max_velocity.is_zero() { 0 } else { num_steps };
+                motion.retarget::<Exponent>(num_steps);
+                return;
+            }
+        }
+
+        self.motion = if max_velocity.is_zero() || num_steps == 0 {
+            None
+        } else {
+            Some(plan(self.time_constant, max_velocity, num_steps))
+        };
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        self.motion.as_mut()?.next_delay::<Exponent>()
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.motion.as_ref().map_or(0, Motion::steps_remaining)
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        self.motion
+            .as_ref()
+            .map_or(Num::zero(), Motion::current_velocity)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.motion.as_ref().is_none_or(Motion::is_idle)
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        self.motion.as_ref().map_or(0, Motion::steps_to_stop)
+    }
+}
+
+/// The default numeric type used by [`Exponential`]
+pub type DefaultNum = fixed::FixedU64<typenum::U32>;
+
+/// Plan a motion from a stand-still
+///
+/// The ramp up is parametrized by the normalized time `x = t / τ`. The position
+/// on the ramp is `k * (x - 1 + e^(-x))`, with `k = v_∞ * τ`, where `v_∞` is
+/// the velocity the ramp approaches.
+fn plan<Num>(
+    time_constant: Num,
+    max_velocity: Num,
+    num_steps: u32,
+) -> Motion<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + Exp
+        + Ln
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    // The velocity reaches 99% of `v_∞` after `ln(100)` time constants. Choose
+    // `v_∞` so that's exactly the maximum velocity.
+    let velocity = max_velocity * 100.az::<Num>() / 99.az::<Num>();
+    let k = velocity * time_constant;
+    let ramp_end = 100.az::<Num>().ln();
+
+    Motion::plan::<Exponent>(
+        k,
+        time_constant,
+        ramp_end,
+        max_velocity,
+        num_steps,
+    )
+}
+
+/// The shape of the ramp, `x - 1 + e^(-x)`
+struct Exponent;
+
+impl<Num> Shape<Num> for Exponent
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + Exp,
+{
+    fn distance(x: Num) -> Num {
+        sub(x + x.exp().inv(), Num::one())
+    }
+
+    fn slope(x: Num) -> Num {
+        sub(Num::one(), x.exp().inv())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{Exponential, MotionProfile as _};
+
+    #[test]
+    fn exponential_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<Exponential<f32>>();
+    }
+
+    #[test]
+    fn exponential_should_follow_exponential_velocity() {
+        let time_constant = 0.05;
+        let max_velocity = 1000.0;
+
+        let mut exponential = Exponential::<f64>::new(time_constant);
+        exponential.enter_position_mode(max_velocity, 1000);
+
+        let ramp_time = time_constant * 100f64.ln();
+        let limit = max_velocity / 0.99;
+
+        let mut time = 0.0;
+        for delay in exponential.delays() {
+            let time_mid = time + delay / 2.0;
+            time += delay;
+
+            // The velocity changes during each step, so its average is only an
+            // approximation of the velocity in the middle of the step.
+            if time_mid > 0.01 && time_mid < ramp_time {
+                let expected =
+                    limit * (1.0 - (-time_mid / time_constant).exp());
+                assert_abs_diff_eq!(
+                    1.0 / delay,
+                    expected,
+                    epsilon = expected * 0.01,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exponential_should_cruise_at_max_velocity() {
+        let mut exponential = Exponential::<f64>::new(0.05);
+        exponential.enter_position_mode(1000.0, 1000);
+
+        let velocities: Vec<f64> = exponential.velocities().collect();
+        assert_abs_diff_eq!(velocities[500], 1000.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn exponential_should_blend_into_cruise() {
+        let mut exponential = Exponential::<f64>::new(0.05);
+        exponential.enter_position_mode(1000.0, 1000);
+
+        // Towards the end of the ramp, the acceleration is 1-2% of its initial
+        // value, which is 20000 steps/s². Jumping to the maximum velocity from
+        // 99% of it would change the velocity by 10 steps/s in a single step.
+        let velocities: Vec<f64> = exponential.velocities().collect();
+        for pair in velocities[..500].windows(2) {
+            if pair[0] > 990.0 {
+                assert!(pair[1] - pair[0] < 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn exponential_should_be_symmetric() {
+        crate::util::testing::ramp_must_be_symmetric(Exponential::<f64>::new(
+            0.05,
+        ));
+    }
+
+    #[test]
+    fn exponential_should_work_with_fixed_point_numbers() {
+        use fixed::types::U32F32;
+
+        crate::util::testing::fixed_point_must_match_floating_point(
+            Exponential::<f64>::new(0.05),
+            Exponential::<U32F32>::new(U32F32::from_num(0.05)),
+        );
+    }
+}
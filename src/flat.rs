@@ -41,9 +41,22 @@ use crate::MotionProfile;
 /// be ideal for 8- or 16-bit microcontrollers, or target platforms where
 /// hardware support for floating point numbers is available. You can override
 /// it with other types from the `fixed` crate, or `f32`/`f64`, for example.
+///
+/// # Serialization
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`. Since it contains the complete state of the motion,
+/// a deserialized instance will continue exactly where the serialized one
+/// stopped.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Flat<Num = DefaultNum> {
     delay: Option<Num>,
     num_steps: u32,
+
+    /// Whether a step has been made since the motion profile was last idle
+    moving: bool,
 }
 
 impl<Num> Flat<Num> {
@@ -52,6 +65,7 @@ impl<Num> Flat<Num> {
         Self {
             delay: None,
             num_steps: 0,
+            moving: false,
         }
     }
 }
@@ -74,6 +88,10 @@ where
         max_velocity: Self::Velocity,
         num_steps: u32,
     ) {
+        if self.is_idle() {
+            self.moving = false;
+        }
+
         self.delay = if max_velocity.is_zero() {
             None
         } else {
@@ -89,9 +107,30 @@ where
         }
 
         self.num_steps -= 1;
+        self.moving = true;
 
         self.delay
     }
+
+    fn steps_remaining(&self) -> u32 {
+        self.num_steps
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        match self.delay {
+            Some(delay) if self.moving && !self.is_idle() => delay.inv(),
+            _ => Num::zero(),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.num_steps == 0 || self.delay.is_none()
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        // This profile stops instantly.
+        0
+    }
 }
 
 /// The default numeric type used by [`Flat`]
@@ -117,4 +156,19 @@ mod tests {
             assert_eq!(velocity, max_velocity);
         }
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn flat_should_continue_after_serialization_roundtrip() {
+        let mut flat = Flat::<f32>::new();
+
+        flat.enter_position_mode(1000.0, 200);
+        flat.delays().take(50).for_each(drop);
+
+        let serialized = serde_json::to_string(&flat).unwrap();
+        let mut restored: Flat<f32> =
+            serde_json::from_str(&serialized).unwrap();
+
+        assert!(flat.delays().eq(restored.delays()));
+    }
 }
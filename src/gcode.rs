@@ -0,0 +1,661 @@
+//! Minimal G-code interpreter
+//!
+//! This module interprets a practical subset of G-code, one line at a time,
+//! and turns it into [`Segment`]s, which can be executed using an
+//! [`Interpolator`]. This is enough to drive small plotters, laser cutters, or
+//! pick-and-place machines. It works without the standard library, and doesn't
+//! allocate.
+//!
+//! See [`Interpreter`].
+//!
+//! This module is only available, if the **`gcode`** feature is enabled.
+
+use core::ops;
+
+use num_traits::{One, Zero};
+
+use crate::{
+    interpolation::{self, Interpolator, Rotation},
+    util::traits::{Atan2, Ceil, Cos, Sin, Sqrt},
+    MotionProfile,
+};
+
+/// Interprets G-code, one line at a time
+///
+/// Create an instance of this struct using [`Interpreter::new`], then pass
+/// each line to [`Interpreter::execute`]. If the line results in a motion, a
+/// [`Segment`] is returned, which can be passed to an [`Interpolator`] using
+/// [`Segment::enter`].
+///
+/// The following codes are supported:
+///
+/// - `G0`: Rapid motion to `X`/`Y`, at [`Config::rapid_feed_rate`].
+/// - `G1`: Linear motion to `X`/`Y`, at the feed rate set by `F`.
+/// - `G2`/`G3`: Clockwise/counterclockwise arc to `X`/`Y`, around the center
+///   given by the offsets `I`/`J`, at the feed rate set by `F`.
+/// - `G17`: Select the XY plane. As this is the only supported plane, this
+///   does nothing.
+/// - `G20`/`G21`: Select inches/millimeters as unit.
+/// - `G90`/`G91`: Select absolute/incremental coordinates. Arc centers are
+///   always relative to the start of the arc.
+/// - `F`: Set the feed rate, in units per minute.
+/// - `M`: Passed to the callback provided to [`Interpreter::execute`].
+/// - `N`: Line numbers are ignored.
+///
+/// Comments in parentheses, and everything after a semicolon, are ignored.
+/// Motion codes are modal, which means lines that only contain coordinates
+/// continue the last motion. Anything else results in an error.
+///
+/// # Units
+///
+/// The interpreter keeps track of the position in millimeters, and converts it
+/// to steps using [`Config::steps_per_mm`]. As both axes are driven by the
+/// same [`Interpolator`], they must have the same resolution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Interpreter {
+    config: Config,
+    position: (f32, f32),
+    state: State,
+}
+
+impl Interpreter {
+    /// Create a new instance of `Interpreter`
+    ///
+    /// Starts out at position `(0, 0)`, using millimeters and absolute
+    /// coordinates, without a feed rate.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            position: (0.0, 0.0),
+            state: State {
+                motion: Motion::Rapid,
+                inches: false,
+                incremental: false,
+                feed_rate: None,
+            },
+        }
+    }
+
+    /// Return the position after the last segment, in millimeters
+    pub fn position(&self) -> (f32, f32) {
+        self.position
+    }
+
+    /// Interpret a line of G-code
+    ///
+    /// Returns the segment that results from the line, if any. `on_m_code` is
+    /// called with the number of each M-code in the line, in order, before the
+    /// segment is returned.
+    ///
+    /// If an error is returned, the line has no effect, and `on_m_code` is not
+    /// called.
+    pub fn execute(
+        &mut self,
+        line: &str,
+        mut on_m_code: impl FnMut(u16),
+    ) -> Result<Option<Segment>, Error> {
+        let mut state = self.state;
+        let mut motion = None;
+        let mut x = None;
+        let mut y = None;
+        let mut i = None;
+        let mut j = None;
+        let mut f = None;
+
+        for word in Words::new(line) {
+            let (letter, value) = word?;
+            match letter {
+                'G' => match code(letter, value)? {
+                    0 => motion = Some(Motion::Rapid),
+                    1 => motion = Some(Motion::Linear),
+                    2 => motion = Some(Motion::Arc(Rotation::Clockwise)),
+                    3 => motion = Some(Motion::Arc(Rotation::Counterclockwise)),
+                    17 => {}
+                    20 => state.inches = true,
+                    21 => state.inches = false,
+                    90 => state.incremental = false,
+                    91 => state.incremental = true,
+                    code => return Err(Error::UnsupportedCode(letter, code)),
+                },
+                'M' => {
+                    code(letter, value)?;
+                }
+                'X' => x = Some(value),
+                'Y' => y = Some(value),
+                'I' => i = Some(value),
+                'J' => j = Some(value),
+                'F' => f = Some(value),
+                'N' => {}
+                letter => return Err(Error::UnsupportedWord(letter)),
+            }
+        }
+
+        let scale = if state.inches { 25.4 } else { 1.0 };
+        if let Some(f) = f {
+            // Written to also reject values that can't be compared, like NaN.
+            let valid_feed_rate = f > 0.0;
+            if !valid_feed_rate {
+                return Err(Error::InvalidFeedRate);
+            }
+            state.feed_rate = Some(f * scale);
+        }
+        if let Some(motion) = motion {
+            state.motion = motion;
+        }
+
+        let is_arc = matches!(state.motion, Motion::Arc(_));
+        let has_motion = x.is_some()
+            || y.is_some()
+            || is_arc && (i.is_some() || j.is_some());
+
+        let mut segment = None;
+        let mut position = self.position;
+        if has_motion {
+            let feed_rate = match state.motion {
+                Motion::Rapid => self.config.rapid_feed_rate,
+                _ => state.feed_rate.ok_or(Error::MissingFeedRate)?,
+            };
+
+            let target = |axis: Option<f32>, current: f32| match axis {
+                Some(axis) if state.incremental => current + axis * scale,
+                Some(axis) => axis * scale,
+                None => current,
+            };
+            position = (target(x, position.0), target(y, position.1));
+
+            let feed_rate = feed_rate * self.config.steps_per_mm
+                / self.config.time_units_per_minute;
+            let end = (self.steps(position.0)?, self.steps(position.1)?);
+
+            segment = Some(match state.motion {
+                Motion::Rapid | Motion::Linear => {
+                    Segment::Line { end, feed_rate }
+                }
+                Motion::Arc(rotation) => {
+                    if i.is_none() && j.is_none() {
+                        return Err(Error::MissingCenter);
+                    }
+
+                    let steps_per_mm = self.config.steps_per_mm;
+                    let center = |offset: Option<f32>, start: f32| {
+                        (start + offset.unwrap_or(0.0) * scale) * steps_per_mm
+                    };
+
+                    Segment::Arc {
+                        center: (
+                            center(i, self.position.0),
+                            center(j, self.position.1),
+                        ),
+                        end,
+                        rotation,
+                        feed_rate,
+                    }
+                }
+            });
+        }
+
+        for word in Words::new(line) {
+            if let Ok(('M', value)) = word {
+                // Has already been validated above.
+                if let Ok(code) = code('M', value) {
+                    on_m_code(code);
+                }
+            }
+        }
+
+        self.state = state;
+        self.position = position;
+
+        Ok(segment)
+    }
+
+    fn steps(&self, mm: f32) -> Result<i32, Error> {
+        // Round to the nearest step. Can't use `f32::round`, as that's not
+        // available without the standard library.
+        let steps = mm * self.config.steps_per_mm;
+        let steps = if steps >= 0.0 {
+            steps + 0.5
+        } else {
+            steps - 0.5
+        };
+
+        // The cast below would saturate silently, so values outside of the
+        // range of `i32` (and NaN) are rejected with `Error::OutOfRange`
+        // before it. Values within the range are truncated to a valid `i32`.
+        let range = i32::MIN as f32..-(i32::MIN as f32);
+        if !range.contains(&steps) {
+            return Err(Error::OutOfRange);
+        }
+
+        Ok(steps as i32)
+    }
+}
+
+/// The configuration of an [`Interpreter`]
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Config {
+    /// The resolution of both axes, in steps per millimeter
+    pub steps_per_mm: f32,
+
+    /// The number of units of time per minute
+    ///
+    /// Feed rates in G-code are given per minute, while motion profiles work
+    /// with velocities in steps per unit of time. If your delays are in
+    /// seconds, this would be `60.0`. If they are in timer ticks, this would be
+    /// the number of timer ticks per minute.
+    pub time_units_per_minute: f32,
+
+    /// The feed rate used for `G0`, in millimeters per minute
+    pub rapid_feed_rate: f32,
+}
+
+/// A motion that results from a line of G-code
+///
+/// Returned by [`Interpreter::execute`]. All positions are in steps, all
+/// feed rates in steps per unit of time.
+///
+/// If the **`serde`** feature is enabled, this enum implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Segment {
+    /// A straight motion
+    ///
+    /// See [`Interpolator::enter_line`].
+    Line {
+        /// The end of the line
+        end: (i32, i32),
+
+        /// The maximum velocity along the line
+        feed_rate: f32,
+    },
+
+    /// A circular motion
+    ///
+    /// See [`Interpolator::enter_arc`].
+    Arc {
+        /// The center of the arc
+        center: (f32, f32),
+
+        /// The end of the arc
+        end: (i32, i32),
+
+        /// The direction of rotation
+        rotation: Rotation,
+
+        /// The maximum velocity along the arc
+        feed_rate: f32,
+    },
+}
+
+impl Segment {
+    /// Start the motion of this segment on the given interpolator
+    ///
+    /// The interpolator must be at the position where the segment starts,
+    /// which is the case, if it has executed all previous segments.
+    pub fn enter<Profile, Num>(
+        &self,
+        interpolator: &mut Interpolator<Profile, Num>,
+    ) -> Result<(), interpolation::Error>
+    where
+        Profile: MotionProfile,
+        Profile::Velocity: PartialOrd + Zero,
+        Profile::Delay: Copy + ops::Add<Output = Profile::Delay>,
+        Num: Copy
+            + PartialOrd
+            + Zero
+            + One
+            + ops::Add<Output = Num>
+            + ops::Sub<Output = Num>
+            + ops::Mul<Output = Num>
+            + ops::Div<Output = Num>
+            + Atan2
+            + Ceil
+            + Cos
+            + Sin
+            + Sqrt
+            + az::Cast<u32>,
+        i32: az::Cast<Num>,
+        u32: az::Cast<Num>,
+        f32: az::Cast<Num> + az::Cast<Profile::Velocity>,
+    {
+        match *self {
+            Segment::Line { end, feed_rate } => {
+                interpolator.enter_line(end, az::cast(feed_rate))
+            }
+            Segment::Arc {
+                center,
+                end,
+                rotation,
+                feed_rate,
+            } => interpolator.enter_arc(
+                (az::cast(center.0), az::cast(center.1)),
+                end,
+                rotation,
+                az::cast(feed_rate),
+            ),
+        }
+    }
+}
+
+/// An error that occurred while interpreting a line of G-code
+///
+/// Returned by [`Interpreter::execute`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The line contains a character that doesn't start a word
+    UnexpectedCharacter(char),
+
+    /// A word has no number, or one that can't be parsed
+    InvalidNumber(char),
+
+    /// The letter of a word is not supported
+    UnsupportedWord(char),
+
+    /// The code is not supported
+    UnsupportedCode(char, u16),
+
+    /// A feed motion was requested, before a feed rate was set
+    MissingFeedRate,
+
+    /// The feed rate is not above zero
+    InvalidFeedRate,
+
+    /// A coordinate is too large to be represented in steps
+    OutOfRange,
+
+    /// An arc was requested, without an `I` or `J` word for its center
+    MissingCenter,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct State {
+    motion: Motion,
+    inches: bool,
+    incremental: bool,
+    feed_rate: Option<f32>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Motion {
+    Rapid,
+    Linear,
+    Arc(Rotation),
+}
+
+/// Iterator over the words of a line, as pairs of letter and number
+struct Words<'r> {
+    line: &'r str,
+    pos: usize,
+}
+
+impl<'r> Words<'r> {
+    fn new(line: &'r str) -> Self {
+        Self { line, pos: 0 }
+    }
+}
+
+impl Iterator for Words<'_> {
+    type Item = Result<(char, f32), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let bytes = self.line.as_bytes();
+
+        loop {
+            let c = *bytes.get(self.pos)?;
+            match c {
+                b' ' | b'\t' | b'\r' | b'\n' => self.pos += 1,
+                b'(' => {
+                    while bytes.get(self.pos).is_some_and(|&c| c != b')') {
+                        self.pos += 1;
+                    }
+                    self.pos += 1;
+                }
+                b';' => {
+                    self.pos = bytes.len();
+                    return None;
+                }
+                c if c.is_ascii_alphabetic() => break,
+                _ => {
+                    // Skip the rest of the line, so iteration ends after the
+                    // error.
+                    let c = self.line[self.pos..].chars().next();
+                    self.pos = bytes.len();
+                    return c.map(|c| Err(Error::UnexpectedCharacter(c)));
+                }
+            }
+        }
+
+        let letter = char::from(bytes[self.pos].to_ascii_uppercase());
+        self.pos += 1;
+
+        let start = self.pos;
+        while bytes
+            .get(self.pos)
+            .is_some_and(|&c| c.is_ascii_digit() || b"+-.".contains(&c))
+        {
+            self.pos += 1;
+        }
+
+        match self.line[start..self.pos].parse() {
+            Ok(value) => Some(Ok((letter, value))),
+            Err(_) => {
+                self.pos = bytes.len();
+                Some(Err(Error::InvalidNumber(letter)))
+            }
+        }
+    }
+}
+
+/// Convert the number of a word to a code, like the `1` in `G1`
+fn code(letter: char, value: f32) -> Result<u16, Error> {
+    let code = value as u16;
+    if value < 0.0 || f32::from(code) != value {
+        return Err(Error::InvalidNumber(letter));
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{interpolation::Interpolator, Trapezoidal};
+
+    use super::{Config, Error, Interpreter, Rotation, Segment};
+
+    fn interpreter() -> Interpreter {
+        Interpreter::new(Config {
+            steps_per_mm: 10.0,
+            time_units_per_minute: 60.0,
+            rapid_feed_rate: 6000.0,
+        })
+    }
+
+    fn execute(
+        interpreter: &mut Interpreter,
+        line: &str,
+    ) -> Result<Option<Segment>, Error> {
+        interpreter.execute(line, |_| {})
+    }
+
+    #[test]
+    fn interpreter_should_convert_lines_to_steps() {
+        let mut interpreter = interpreter();
+
+        let segment = execute(&mut interpreter, "G0 X10 Y-2.5").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Line {
+                end: (100, -25),
+                feed_rate: 1000.0,
+            })
+        );
+
+        let segment = execute(&mut interpreter, "G1 X20 F600").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Line {
+                end: (200, -25),
+                feed_rate: 100.0,
+            })
+        );
+
+        // Motion is modal.
+        let segment = execute(&mut interpreter, "Y0").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Line {
+                end: (200, 0),
+                feed_rate: 100.0,
+            })
+        );
+        assert_eq!(interpreter.position(), (20.0, 0.0));
+    }
+
+    #[test]
+    fn interpreter_should_support_incremental_coordinates_and_inches() {
+        let mut interpreter = interpreter();
+
+        execute(&mut interpreter, "G91").unwrap();
+        execute(&mut interpreter, "G0 X1 Y1").unwrap();
+        let segment = execute(&mut interpreter, "G0 X1 Y1").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Line {
+                end: (20, 20),
+                feed_rate: 1000.0,
+            })
+        );
+
+        let segment = execute(&mut interpreter, "G20 G90 G1 X1 F10").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Line {
+                end: (254, 20),
+                feed_rate: 10.0 * 25.4 * 10.0 / 60.0,
+            })
+        );
+    }
+
+    #[test]
+    fn interpreter_should_convert_arcs() {
+        let mut interpreter = interpreter();
+
+        execute(&mut interpreter, "G0 X10 Y0").unwrap();
+        let segment =
+            execute(&mut interpreter, "G3 X0 Y10 I-10 J0 F600").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Arc {
+                center: (0.0, 0.0),
+                end: (0, 100),
+                rotation: Rotation::Counterclockwise,
+                feed_rate: 100.0,
+            })
+        );
+
+        // Full circle, without end point.
+        let segment = execute(&mut interpreter, "G2 J-5").unwrap();
+        assert_eq!(
+            segment,
+            Some(Segment::Arc {
+                center: (0.0, 50.0),
+                end: (0, 100),
+                rotation: Rotation::Clockwise,
+                feed_rate: 100.0,
+            })
+        );
+    }
+
+    #[test]
+    fn interpreter_should_pass_m_codes_to_callback() {
+        let mut interpreter = interpreter();
+
+        let mut codes = Vec::new();
+        let segment = interpreter
+            .execute("N10 M3 G0 X1 M7 (comment M8) ; M9", |code| {
+                codes.push(code)
+            })
+            .unwrap();
+
+        assert_eq!(codes, [3, 7]);
+        assert!(segment.is_some());
+    }
+
+    #[test]
+    fn interpreter_should_ignore_lines_without_motion() {
+        let mut interpreter = interpreter();
+
+        assert_eq!(execute(&mut interpreter, ""), Ok(None));
+        assert_eq!(execute(&mut interpreter, "(comment)"), Ok(None));
+        assert_eq!(execute(&mut interpreter, "g21 f100"), Ok(None));
+        assert_eq!(execute(&mut interpreter, "G1"), Ok(None));
+    }
+
+    #[test]
+    fn interpreter_should_reject_invalid_lines_without_effect() {
+        let mut interpreter = interpreter();
+
+        let mut called = false;
+        let result = interpreter.execute("M3 G1 X10", |_| called = true);
+        assert_eq!(result, Err(Error::MissingFeedRate));
+        assert!(!called);
+        assert_eq!(interpreter.position(), (0.0, 0.0));
+
+        let errors = [
+            ("G0 Z1", Error::UnsupportedWord('Z')),
+            ("G4 P1", Error::UnsupportedCode('G', 4)),
+            ("G1.5", Error::InvalidNumber('G')),
+            ("X", Error::InvalidNumber('X')),
+            ("X1 #", Error::UnexpectedCharacter('#')),
+            ("G2 X1 F100", Error::MissingCenter),
+            ("G1 X1 F0", Error::InvalidFeedRate),
+            ("G1 X1 F-100", Error::InvalidFeedRate),
+            ("G0 X300000000", Error::OutOfRange),
+            ("G0 Y-300000000", Error::OutOfRange),
+        ];
+        for (line, error) in errors.iter() {
+            assert_eq!(execute(&mut interpreter, line), Err(*error));
+        }
+        assert_eq!(interpreter.position(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn segments_should_drive_interpolator() {
+        let program = [
+            "G21 G90 F600",
+            "G1 X10",
+            "G3 X0 Y10 I-10",
+            "G91 G1 X-5 Y-5",
+            "G90 G0 X0 Y0",
+        ];
+
+        let mut interpreter = interpreter();
+        let mut interpolator: Interpolator<Trapezoidal<f32>, f32> =
+            Interpolator::new(Trapezoidal::new(1000.0));
+
+        for line in program.iter() {
+            if let Some(segment) = execute(&mut interpreter, line).unwrap() {
+                segment.enter(&mut interpolator).unwrap();
+                while interpolator.next_step().is_some() {}
+
+                let (x, y) = interpreter.position();
+                assert_eq!(
+                    interpolator.position(),
+                    (x as i32 * 10, y as i32 * 10)
+                );
+            }
+        }
+
+        assert_eq!(interpolator.position(), (0, 0));
+    }
+}
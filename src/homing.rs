@@ -0,0 +1,415 @@
+//! Homing sequence
+//!
+//! See [`Homing`].
+
+use crate::{Direction, MotionProfile};
+
+/// Runs a homing sequence on top of a [`MotionProfile`]
+///
+/// Homing establishes a reference position for an axis by moving it towards a
+/// limit switch. This struct implements the standard multi-phase sequence:
+///
+/// 1. Seek: Move towards the switch at [`Config::seek_velocity`], until
+///    [`Homing::switch_triggered`] is called.
+/// 2. Decelerate: Come to a stop, using the ramp of the motion profile.
+/// 3. Back off: Move away from the switch, until the axis is
+///    [`Config::back_off_steps`] steps away from the position where the switch
+///    triggered.
+/// 4. Approach: Move towards the switch again at
+///    [`Config::approach_velocity`], until [`Homing::switch_triggered`] is
+///    called a second time. The position at that point is latched and can be
+///    queried using [`Homing::latched_position`].
+/// 5. Decelerate: Come to a stop again.
+///
+/// Create an instance of this struct using [`Homing::new`], then call
+/// [`Homing::next_step`] to get the direction and delay of each step. Call
+/// [`Homing::switch_triggered`] whenever the switch gets triggered.
+///
+/// # Position
+///
+/// All positions reported by this struct are in steps, relative to the
+/// position of the axis when homing started. Steps in [`Direction::Forward`]
+/// increase the position.
+///
+/// # Motion Profile
+///
+/// To stop after the switch triggers, [`MotionProfile::enter_position_mode`]
+/// is called with a target of zero steps. All motion profiles in this library
+/// support that, but they differ in how quickly they come to a stop. [`Pvt`],
+/// for example, can't cut its trajectory short.
+///
+/// When the switch triggers, [`MotionProfile::steps_to_stop`] is compared to
+/// [`Config::max_overtravel`], and the homing sequence fails right away, if
+/// the axis can't come to a stop in time.
+///
+/// [`Pvt`]: crate::Pvt
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Homing<Profile: MotionProfile> {
+    profile: Profile,
+    config: Config<Profile::Velocity>,
+    phase: Phase,
+    position: i32,
+    trigger_position: i32,
+    latched_position: Option<i32>,
+}
+
+impl<Profile> Homing<Profile>
+where
+    Profile: MotionProfile,
+    Profile::Velocity: Copy,
+{
+    /// Create a new instance of `Homing` and start seeking the switch
+    ///
+    /// Returns the motion profile as an error, if it is not idle. Homing must
+    /// start from a stand-still, as the position is relative to where it
+    /// started.
+    pub fn new(
+        mut profile: Profile,
+        config: Config<Profile::Velocity>,
+    ) -> Result<Self, Profile> {
+        if !profile.is_idle() {
+            return Err(profile);
+        }
+
+        profile
+            .enter_position_mode(config.seek_velocity, config.max_seek_steps);
+
+        Ok(Self {
+            profile,
+            config,
+            phase: Phase::Seek,
+            position: 0,
+            trigger_position: 0,
+            latched_position: None,
+        })
+    }
+
+    /// Notify the homing sequence that the switch has been triggered
+    ///
+    /// Call this, whenever the switch changes from released to triggered. The
+    /// event is ignored, unless the homing sequence is seeking or approaching
+    /// the switch.
+    ///
+    /// If the axis can't come to a stop within [`Config::max_overtravel`]
+    /// steps, the homing sequence fails immediately.
+    pub fn switch_triggered(&mut self) {
+        if !matches!(self.phase, Phase::Seek | Phase::Approach) {
+            return;
+        }
+        if self.profile.steps_to_stop() > self.config.max_overtravel {
+            self.phase = Phase::Failed(Error::Overtravel);
+            return;
+        }
+
+        let (phase, velocity) = match self.phase {
+            Phase::Seek => (Phase::StopAfterSeek, self.config.seek_velocity),
+            Phase::Approach => {
+                self.latched_position = Some(self.position);
+                (Phase::StopAfterApproach, self.config.approach_velocity)
+            }
+            _ => return,
+        };
+
+        self.phase = phase;
+        self.trigger_position = self.position;
+        self.profile.enter_position_mode(velocity, 0);
+    }
+
+    /// Return the direction and delay of the next step
+    ///
+    /// Returns `Ok(None)`, once the homing sequence has finished. Returns an
+    /// error, if the switch was not found, or if the axis would have to move
+    /// more than [`Config::max_overtravel`] steps past the switch to come to a
+    /// stop. The latter is usually detected in [`Homing::switch_triggered`]
+    /// already, but the step count is still checked while stopping. Once an
+    /// error has been returned, every following call returns the same error.
+    pub fn next_step(
+        &mut self,
+    ) -> Result<Option<(Direction, Profile::Delay)>, Error> {
+        loop {
+            match self.phase {
+                Phase::Done => return Ok(None),
+                Phase::Failed(err) => return Err(err),
+                _ => {}
+            }
+
+            let direction = self.direction();
+
+            if let Some(delay) = self.profile.next_delay() {
+                let position = self.position + direction.sign();
+
+                let stopping = matches!(
+                    self.phase,
+                    Phase::StopAfterSeek | Phase::StopAfterApproach
+                );
+                let overtravel =
+                    (position - self.trigger_position).unsigned_abs();
+                if stopping && overtravel > self.config.max_overtravel {
+                    self.phase = Phase::Failed(Error::Overtravel);
+                    continue;
+                }
+
+                self.position = position;
+                return Ok(Some((direction, delay)));
+            }
+
+            // The motion of the current phase has ended. Move on to the next.
+            self.phase = match self.phase {
+                Phase::StopAfterSeek => {
+                    let overtravel =
+                        (self.position - self.trigger_position).unsigned_abs();
+                    self.profile.enter_position_mode(
+                        self.config.seek_velocity,
+                        overtravel + self.config.back_off_steps,
+                    );
+                    Phase::BackOff
+                }
+                Phase::BackOff => {
+                    self.profile.enter_position_mode(
+                        self.config.approach_velocity,
+                        self.config.back_off_steps + self.config.max_overtravel,
+                    );
+                    Phase::Approach
+                }
+                Phase::StopAfterApproach => Phase::Done,
+                _ => Phase::Failed(Error::SwitchNotFound),
+            };
+        }
+    }
+
+    /// Return the current position
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Return the position at which the switch triggered during the approach
+    ///
+    /// Returns `None`, until the switch has triggered during the approach.
+    pub fn latched_position(&self) -> Option<i32> {
+        self.latched_position
+    }
+
+    /// Indicate whether the homing sequence has finished successfully
+    pub fn is_done(&self) -> bool {
+        matches!(self.phase, Phase::Done)
+    }
+
+    /// Release the motion profile
+    pub fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    fn direction(&self) -> Direction {
+        match self.phase {
+            Phase::BackOff => self.config.direction.reverse(),
+            _ => self.config.direction,
+        }
+    }
+}
+
+/// The configuration of a homing sequence
+///
+/// Passed to [`Homing::new`].
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Config<Velocity> {
+    /// The direction in which the switch is located
+    pub direction: Direction,
+
+    /// The maximum velocity while seeking the switch, and while backing off
+    pub seek_velocity: Velocity,
+
+    /// The maximum velocity while approaching the switch a second time
+    ///
+    /// This should be lower than the seek velocity, to get a more precise
+    /// latched position.
+    pub approach_velocity: Velocity,
+
+    /// The distance from the switch after backing off, in steps
+    pub back_off_steps: u32,
+
+    /// The maximum number of steps to move while seeking the switch
+    pub max_seek_steps: u32,
+
+    /// The maximum number of steps the axis may move past the switch
+    ///
+    /// Bounds the distance needed to come to a stop after the switch
+    /// triggered. This is also the number of steps the approach may move past
+    /// the position where the switch triggered during the seek.
+    pub max_overtravel: u32,
+}
+
+/// An error that occurred during a homing sequence
+///
+/// Returned by [`Homing::next_step`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The switch did not trigger within the allowed distance
+    SwitchNotFound,
+
+    /// The axis could not come to a stop within the allowed distance
+    Overtravel,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Phase {
+    Seek,
+    StopAfterSeek,
+    BackOff,
+    Approach,
+    StopAfterApproach,
+    Done,
+    Failed(Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, Flat, MotionProfile as _, Trapezoidal};
+
+    use super::{Config, Error, Homing};
+
+    fn config() -> Config<f32> {
+        Config {
+            direction: Direction::Backward,
+            seek_velocity: 1000.0,
+            approach_velocity: 200.0,
+            back_off_steps: 50,
+            max_seek_steps: 10_000,
+            max_overtravel: 200,
+        }
+    }
+
+    /// Runs the homing sequence against a switch that is triggered at and
+    /// below `switch_position`
+    fn run<Profile>(
+        homing: &mut Homing<Profile>,
+        switch_position: i32,
+    ) -> Result<Vec<(Direction, Profile::Delay)>, Error>
+    where
+        Profile: crate::MotionProfile<Velocity = f32>,
+    {
+        let mut steps = Vec::new();
+        let mut triggered = homing.position() <= switch_position;
+
+        while let Some(step) = homing.next_step()? {
+            steps.push(step);
+
+            let triggered_now = homing.position() <= switch_position;
+            if triggered_now && !triggered {
+                homing.switch_triggered();
+            }
+            triggered = triggered_now;
+        }
+
+        Ok(steps)
+    }
+
+    #[test]
+    fn homing_should_latch_position_of_switch() {
+        let switch_position = -3000;
+
+        let profile = Trapezoidal::new(6000.0);
+        let mut homing = Homing::new(profile, config()).unwrap();
+        let steps = run(&mut homing, switch_position).unwrap();
+
+        assert!(homing.is_done());
+        assert_eq!(homing.latched_position(), Some(switch_position));
+        assert!(homing.position() <= switch_position);
+        assert!(homing.position() >= switch_position - 200);
+
+        // Must have backed off once, then approached the switch again.
+        let reversals = steps.windows(2).filter(|w| w[0].0 != w[1].0).count();
+        assert_eq!(reversals, 2);
+    }
+
+    #[test]
+    fn homing_should_back_off_past_trigger_position() {
+        let switch_position = -3000;
+
+        let profile = Trapezoidal::new(6000.0);
+        let mut homing = Homing::new(profile, config()).unwrap();
+        let steps = run(&mut homing, switch_position).unwrap();
+
+        let mut position = 0;
+        let mut back_off_end = None;
+        for w in steps.windows(2) {
+            position += w[0].0.sign();
+            if w[0].0 == Direction::Forward && w[1].0 == Direction::Backward {
+                back_off_end = Some(position);
+            }
+        }
+
+        assert_eq!(back_off_end, Some(switch_position + 50));
+    }
+
+    #[test]
+    fn homing_should_approach_at_approach_velocity() {
+        let profile = Trapezoidal::new(6000.0);
+        let mut homing = Homing::new(profile, config()).unwrap();
+        let steps = run(&mut homing, -3000).unwrap();
+
+        let approach = steps
+            .iter()
+            .rev()
+            .take_while(|(direction, _)| *direction == Direction::Backward);
+        for (_, delay) in approach {
+            assert!(delay.recip() <= 200.0);
+        }
+    }
+
+    #[test]
+    fn homing_should_fail_if_switch_is_not_found() {
+        let profile = Trapezoidal::new(6000.0);
+        let mut homing = Homing::new(profile, config()).unwrap();
+
+        let steps = run(&mut homing, -20_000);
+        assert_eq!(steps, Err(Error::SwitchNotFound));
+        assert_eq!(homing.position(), -10_000);
+        assert_eq!(homing.next_step(), Err(Error::SwitchNotFound));
+    }
+
+    #[test]
+    fn homing_should_fail_if_stop_exceeds_overtravel() {
+        let config = Config {
+            max_overtravel: 10,
+            ..config()
+        };
+
+        let profile = Trapezoidal::new(6000.0);
+        let mut homing = Homing::new(profile, config).unwrap();
+
+        let steps = run(&mut homing, -3000);
+        assert_eq!(steps, Err(Error::Overtravel));
+        assert_eq!(homing.position(), -3000);
+        assert!(homing.latched_position().is_none());
+        assert_eq!(homing.next_step(), Err(Error::Overtravel));
+    }
+
+    #[test]
+    fn homing_should_reject_profile_that_is_not_idle() {
+        let mut profile = Trapezoidal::new(6000.0);
+        profile.enter_position_mode(1000.0, 200);
+        profile.next_delay().unwrap();
+
+        let profile = Homing::new(profile, config()).unwrap_err();
+        assert_eq!(profile.steps_remaining(), 199);
+    }
+
+    #[test]
+    fn homing_should_work_with_profile_that_stops_immediately() {
+        let mut homing = Homing::new(Flat::new(), config()).unwrap();
+        run(&mut homing, -3000).unwrap();
+
+        assert!(homing.is_done());
+        assert_eq!(homing.latched_position(), Some(-3000));
+        assert_eq!(homing.position(), -3000);
+        assert!(homing.into_profile().is_idle());
+    }
+}
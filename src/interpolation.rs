@@ -0,0 +1,702 @@
+//! Linear and circular interpolation across two axes
+//!
+//! See [`Interpolator`].
+
+use core::ops;
+
+use az::Az as _;
+use num_traits::{One, Zero};
+
+use crate::{
+    util::traits::{Atan2, Ceil, Cos, Sin, Sqrt},
+    Direction, MotionProfile,
+};
+
+/// Coordinates two axes along lines and circular arcs
+///
+/// Plotters, laser cutters, and similar machines need their tool to follow a
+/// path in the plane, with a controlled velocity along that path (the feed
+/// rate). This struct drives the steps of two axes (X and Y) from a single
+/// [`MotionProfile`], which is run along the length of the path. This means
+/// the velocity along the path follows the ramp of the motion profile, while
+/// the axes make whatever steps are needed to stay on the path.
+///
+/// Create an instance of this struct using [`Interpolator::new`], then start a
+/// motion using [`Interpolator::enter_line`] or [`Interpolator::enter_arc`].
+/// Call [`Interpolator::next_step`] to get the steps of both axes.
+///
+/// # Path Steps
+///
+/// The motion profile works in path steps, which are at most one step long.
+/// After each path step, each axis moves to the step closest to the ideal
+/// path. As a path step can't be longer than a step, each axis makes at most
+/// one step per path step. Path steps that don't move any axis are merged
+/// with the next one, by adding up their delays.
+///
+/// # Arcs
+///
+/// Arcs follow the conventions of G-code (`G2`/`G3`): The arc starts at the
+/// current position, ends at the given end point, and runs around the given
+/// center in the given [`Rotation`]. If the end point is the same as the
+/// current position, a full circle is made. If the end point doesn't lie on
+/// the circle through the current position, the radius changes linearly along
+/// the arc, so the motion always ends exactly on the end point.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used for the geometric
+/// computations. As coordinates relative to the center of an arc can be
+/// negative, it must be a signed type. It is set to a 64-bit fixed-point
+/// number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other signed type
+/// from the `fixed` crate. Please note that you need to enable support for
+/// `f32`/`f64` explicitly. Check out the section on Cargo features from the
+/// documentation in the root module for more information.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Interpolator<Profile: MotionProfile, Num = DefaultNum> {
+    profile: Profile,
+    position: (i32, i32),
+    target: (i32, i32),
+    motion: Option<Motion<Num>>,
+    delay_prev: Option<Profile::Delay>,
+}
+
+impl<Profile, Num> Interpolator<Profile, Num>
+where
+    Profile: MotionProfile,
+    Profile::Velocity: PartialOrd + Zero,
+    Profile::Delay: Copy + ops::Add<Output = Profile::Delay>,
+    Num: Copy
+        + PartialOrd
+        + Zero
+        + One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Atan2
+        + Ceil
+        + Cos
+        + Sin
+        + Sqrt
+        + az::Cast<u32>,
+    i32: az::Cast<Num>,
+    u32: az::Cast<Num>,
+{
+    /// Create a new instance of `Interpolator`
+    ///
+    /// Starts out at position `(0, 0)`.
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            position: (0, 0),
+            target: (0, 0),
+            motion: None,
+            delay_prev: None,
+        }
+    }
+
+    /// Return the current position of both axes, as `(x, y)`
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// Override the current position of both axes
+    ///
+    /// Returns [`Error::NotIdle`], if a motion is in progress.
+    pub fn set_position(&mut self, position: (i32, i32)) -> Result<(), Error> {
+        if !self.is_idle() {
+            return Err(Error::NotIdle);
+        }
+
+        self.position = position;
+        self.target = position;
+        Ok(())
+    }
+
+    /// Start a straight motion from the current position to `end`
+    ///
+    /// `feed_rate` is the maximum velocity along the line, in steps per unit
+    /// of time. Returns [`Error::NotIdle`], if a motion is in progress, or
+    /// [`Error::InvalidFeedRate`], if the feed rate is not above zero.
+    pub fn enter_line(
+        &mut self,
+        end: (i32, i32),
+        feed_rate: Profile::Velocity,
+    ) -> Result<(), Error> {
+        if !self.is_idle() {
+            return Err(Error::NotIdle);
+        }
+
+        let start = point(self.position);
+        let (x, y) = point(end);
+        let length = hypot(x - start.0, y - start.1);
+
+        self.start(Path::Line { start }, end, length, feed_rate)
+    }
+
+    /// Start a circular motion from the current position to `end`
+    ///
+    /// `center` is the center of the arc, in steps. `feed_rate` is the maximum
+    /// velocity along the arc, in steps per unit of time.
+    ///
+    /// Returns [`Error::NotIdle`], if a motion is in progress,
+    /// [`Error::ZeroRadius`], if the current position or the end point
+    /// coincide with the center, or [`Error::InvalidFeedRate`], if the feed
+    /// rate is not above zero.
+    pub fn enter_arc(
+        &mut self,
+        center: (Num, Num),
+        end: (i32, i32),
+        rotation: Rotation,
+        feed_rate: Profile::Velocity,
+    ) -> Result<(), Error> {
+        if !self.is_idle() {
+            return Err(Error::NotIdle);
+        }
+
+        let zero = Num::zero();
+        let (start_x, start_y) = point(self.position);
+        let (end_x, end_y) = point(end);
+
+        let start_radius = hypot(start_x - center.0, start_y - center.1);
+        let end_radius = hypot(end_x - center.0, end_y - center.1);
+        if start_radius == zero || end_radius == zero {
+            return Err(Error::ZeroRadius);
+        }
+
+        let start_angle = (start_y - center.1).atan2(start_x - center.0);
+        let end_angle = (end_y - center.1).atan2(end_x - center.0);
+
+        // Computed like this, instead of using a constant, so it's consistent
+        // with the type's implementation of `Atan2`.
+        let pi = zero.atan2(zero - Num::one());
+        let tau = pi + pi;
+
+        let mut sweep = end_angle - start_angle;
+        let sweep_abs = match rotation {
+            Rotation::Counterclockwise => {
+                if sweep <= zero {
+                    sweep = sweep + tau;
+                }
+                sweep
+            }
+            Rotation::Clockwise => {
+                if sweep >= zero {
+                    sweep = sweep - tau;
+                }
+                zero - sweep
+            }
+        };
+
+        // This is an upper bound for the length of the arc, which makes sure
+        // that no path step is longer than a step, even if the radius changes
+        // along the arc.
+        let (max_radius, radius_change) = if end_radius > start_radius {
+            (end_radius, end_radius - start_radius)
+        } else {
+            (start_radius, start_radius - end_radius)
+        };
+        let length = max_radius * sweep_abs + radius_change;
+
+        let path = Path::Arc {
+            center,
+            start_radius,
+            end_radius,
+            start_angle,
+            sweep,
+        };
+        self.start(path, end, length, feed_rate)
+    }
+
+    /// Return the delay and the steps of both axes for the next step
+    ///
+    /// Returns `None`, once the motion has ended.
+    pub fn next_step(&mut self) -> Option<Step<Profile::Delay>> {
+        let mut delay: Option<Profile::Delay> = None;
+
+        loop {
+            if self.position == self.target {
+                let motion = self.motion.as_mut()?;
+
+                match self.profile.next_delay() {
+                    Some(next) => {
+                        delay = Some(match delay {
+                            Some(delay) => delay + next,
+                            None => next,
+                        });
+                        self.delay_prev = Some(next);
+
+                        motion.path_step += 1;
+                        self.target = motion.target(self.target);
+
+                        if motion.path_step >= motion.path_steps {
+                            self.motion = None;
+                        }
+                    }
+                    None => {
+                        // The motion profile might end the motion before all
+                        // path steps have been made. Catch up with the end.
+                        self.target = motion.end;
+                        self.motion = None;
+                        continue;
+                    }
+                }
+            }
+
+            if self.position != self.target {
+                // Get the delay first, so the position isn't changed without
+                // returning a step.
+                let delay = delay.or(self.delay_prev)?;
+
+                let x = step_towards(&mut self.position.0, self.target.0);
+                let y = step_towards(&mut self.position.1, self.target.1);
+
+                return Some(Step { delay, x, y });
+            }
+        }
+    }
+
+    /// Indicate whether a motion is in progress
+    pub fn is_idle(&self) -> bool {
+        self.motion.is_none() && self.position == self.target
+    }
+
+    /// Access the motion profile
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Release the motion profile
+    pub fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    fn start(
+        &mut self,
+        path: Path<Num>,
+        end: (i32, i32),
+        length: Num,
+        feed_rate: Profile::Velocity,
+    ) -> Result<(), Error> {
+        // Written to also reject values that can't be compared, like NaN.
+        let valid_feed_rate = feed_rate > Profile::Velocity::zero();
+        if !valid_feed_rate {
+            return Err(Error::InvalidFeedRate);
+        }
+
+        let path_steps = length.ceil().az::<u32>();
+        if path_steps == 0 {
+            return Ok(());
+        }
+
+        self.profile.enter_position_mode(feed_rate, path_steps);
+        self.motion = Some(Motion {
+            path,
+            end,
+            path_steps,
+            path_step: 0,
+        });
+
+        Ok(())
+    }
+}
+
+/// The direction of rotation of an arc
+///
+/// Passed to [`Interpolator::enter_arc`]. Assumes that the X axis points to
+/// the right and the Y axis points up.
+///
+/// If the **`serde`** feature is enabled, this enum implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Rotation {
+    /// Clockwise rotation, like G-code's `G2`
+    Clockwise,
+
+    /// Counterclockwise rotation, like G-code's `G3`
+    Counterclockwise,
+}
+
+/// A step of one or both axes
+///
+/// Returned by [`Interpolator::next_step`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Step<Delay> {
+    /// The delay before the step
+    pub delay: Delay,
+
+    /// The direction of the X axis' step, if it makes one
+    pub x: Option<Direction>,
+
+    /// The direction of the Y axis' step, if it makes one
+    pub y: Option<Direction>,
+}
+
+/// An error that can occur when starting a motion
+///
+/// Returned by [`Interpolator::enter_line`] and [`Interpolator::enter_arc`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A motion is already in progress
+    NotIdle,
+
+    /// The start or end point of the arc coincide with its center
+    ZeroRadius,
+
+    /// The feed rate is not above zero
+    InvalidFeedRate,
+}
+
+/// The default numeric type used by [`Interpolator`]
+pub type DefaultNum = fixed::FixedI64<typenum::U32>;
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Motion<Num> {
+    path: Path<Num>,
+    end: (i32, i32),
+    path_steps: u32,
+    path_step: u32,
+}
+
+impl<Num> Motion<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + Zero
+        + One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Cos
+        + Sin,
+    i32: az::Cast<Num>,
+    u32: az::Cast<Num>,
+{
+    /// Return the step closest to the path, after the current path step
+    ///
+    /// Uses `prev` as a starting point, which makes rounding to the closest
+    /// step cheap, as the path only moves by a fraction of a step.
+    fn target(&self, prev: (i32, i32)) -> (i32, i32) {
+        if self.path_step >= self.path_steps {
+            return self.end;
+        }
+
+        let t = self.path_step.az::<Num>() / self.path_steps.az::<Num>();
+
+        let (x, y) = match self.path {
+            Path::Line { start } => {
+                let (end_x, end_y) = point(self.end);
+                (
+                    start.0 + (end_x - start.0) * t,
+                    start.1 + (end_y - start.1) * t,
+                )
+            }
+            Path::Arc {
+                center,
+                start_radius,
+                end_radius,
+                start_angle,
+                sweep,
+            } => {
+                let radius = start_radius + (end_radius - start_radius) * t;
+                let angle = start_angle + sweep * t;
+                (
+                    center.0 + radius * angle.cos(),
+                    center.1 + radius * angle.sin(),
+                )
+            }
+        };
+
+        (closest(prev.0, x), closest(prev.1, y))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Path<Num> {
+    Line {
+        start: (Num, Num),
+    },
+    Arc {
+        center: (Num, Num),
+        start_radius: Num,
+        end_radius: Num,
+        start_angle: Num,
+        sweep: Num,
+    },
+}
+
+fn point<Num>((x, y): (i32, i32)) -> (Num, Num)
+where
+    i32: az::Cast<Num>,
+{
+    (x.az::<Num>(), y.az::<Num>())
+}
+
+/// Compute `sqrt(x^2 + y^2)`, without overflowing for large arguments
+fn hypot<Num>(x: Num, y: Num) -> Num
+where
+    Num: Copy
+        + PartialOrd
+        + Zero
+        + One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Sqrt,
+{
+    let zero = Num::zero();
+    let x = if x < zero { zero - x } else { x };
+    let y = if y < zero { zero - y } else { y };
+
+    let (large, small) = if x > y { (x, y) } else { (y, x) };
+    if large == zero {
+        return zero;
+    }
+
+    let ratio = small / large;
+    large * (Num::one() + ratio * ratio).sqrt()
+}
+
+/// Return the step closest to `x`, starting the search at `step`
+fn closest<Num>(mut step: i32, x: Num) -> i32
+where
+    Num: Copy
+        + PartialOrd
+        + One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Div<Output = Num>,
+    i32: az::Cast<Num>,
+{
+    let half = Num::one() / (Num::one() + Num::one());
+
+    while x - step.az::<Num>() > half {
+        step += 1;
+    }
+    while step.az::<Num>() - x > half {
+        step -= 1;
+    }
+
+    step
+}
+
+fn step_towards(position: &mut i32, target: i32) -> Option<Direction> {
+    let direction = if target > *position {
+        Direction::Forward
+    } else if target < *position {
+        Direction::Backward
+    } else {
+        return None;
+    };
+
+    *position += direction.sign();
+    Some(direction)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, MotionProfile as _, Trapezoidal};
+
+    use super::{Error, Interpolator, Rotation, Step};
+
+    type TestInterpolator = Interpolator<Trapezoidal<f32>, f32>;
+
+    /// Returns the position after each step
+    fn run(interpolator: &mut TestInterpolator) -> Vec<(i32, i32)> {
+        let mut positions = Vec::new();
+        while interpolator.next_step().is_some() {
+            positions.push(interpolator.position());
+        }
+        positions
+    }
+
+    fn distance((x, y): (i32, i32), (cx, cy): (f32, f32)) -> f32 {
+        (x as f32 - cx).hypot(y as f32 - cy)
+    }
+
+    #[test]
+    fn interpolator_should_stay_on_circle() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+        interpolator.set_position((500, 0)).unwrap();
+        interpolator
+            .enter_arc((0.0, 0.0), (500, 0), Rotation::Counterclockwise, 500.0)
+            .unwrap();
+
+        let positions = run(&mut interpolator);
+
+        for &position in &positions {
+            approx::assert_abs_diff_eq!(
+                distance(position, (0.0, 0.0)),
+                500.0,
+                epsilon = 1.0
+            );
+        }
+        assert_eq!(interpolator.position(), (500, 0));
+        assert!(interpolator.is_idle());
+
+        let max_y = positions.iter().map(|&(_, y)| y).max();
+        let min_y = positions.iter().map(|&(_, y)| y).min();
+        assert_eq!(max_y, Some(500));
+        assert_eq!(min_y, Some(-500));
+    }
+
+    #[test]
+    fn interpolator_should_respect_rotation() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+        interpolator.set_position((100, 0)).unwrap();
+        interpolator
+            .enter_arc((0.0, 0.0), (0, 100), Rotation::Counterclockwise, 500.0)
+            .unwrap();
+
+        let positions = run(&mut interpolator);
+        assert!(positions.iter().all(|&(x, y)| x >= 0 && y >= 0));
+        assert_eq!(interpolator.position(), (0, 100));
+
+        interpolator.set_position((100, 0)).unwrap();
+        interpolator
+            .enter_arc((0.0, 0.0), (0, 100), Rotation::Clockwise, 500.0)
+            .unwrap();
+
+        let positions = run(&mut interpolator);
+        assert!(positions.contains(&(0, -100)));
+        assert!(positions.contains(&(-100, 0)));
+        assert_eq!(interpolator.position(), (0, 100));
+    }
+
+    #[test]
+    fn interpolator_should_make_at_most_one_step_per_axis() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+        interpolator.set_position((0, -200)).unwrap();
+        interpolator
+            .enter_arc((0.0, 0.0), (0, -200), Rotation::Clockwise, 500.0)
+            .unwrap();
+
+        let mut steps = 0;
+        let mut position = interpolator.position();
+        while let Some(Step { x, y, .. }) = interpolator.next_step() {
+            let sign = |d: Option<Direction>| d.map_or(0, |d| d.sign());
+            position.0 += sign(x);
+            position.1 += sign(y);
+            assert_eq!(position, interpolator.position());
+            steps += 1;
+        }
+
+        // Number of steps, if the circle were made from straight diagonal
+        // lines, which is the shortest path made up of steps.
+        assert!(steps >= 4 * 200);
+    }
+
+    #[test]
+    fn interpolator_should_follow_ramp_along_path() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+        interpolator.set_position((1000, 0)).unwrap();
+        interpolator
+            .enter_arc((0.0, 0.0), (0, 1000), Rotation::Counterclockwise, 500.0)
+            .unwrap();
+
+        let mut duration = 0.0;
+        while let Some(step) = interpolator.next_step() {
+            duration += step.delay;
+        }
+
+        // The arc is a quarter circle with a radius of 1000 steps.
+        let path_steps = (1000.0 * core::f32::consts::FRAC_PI_2).ceil();
+        let mut profile = Trapezoidal::new(1000.0);
+        profile.enter_position_mode(500.0, path_steps as u32);
+        let expected: f32 = profile.delays().sum();
+
+        approx::assert_abs_diff_eq!(duration, expected, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn interpolator_should_move_along_line() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+        interpolator.enter_line((30, -10), 500.0).unwrap();
+
+        let positions = run(&mut interpolator);
+        for &(x, y) in &positions {
+            approx::assert_abs_diff_eq!(
+                y as f32,
+                -x as f32 / 3.0,
+                epsilon = 1.0
+            );
+        }
+        assert_eq!(interpolator.position(), (30, -10));
+        assert!(positions.len() <= 30);
+    }
+
+    #[test]
+    fn interpolator_should_end_spiral_on_end_point() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+        interpolator.set_position((100, 0)).unwrap();
+        interpolator
+            .enter_arc((0.0, 0.0), (-150, 0), Rotation::Counterclockwise, 500.0)
+            .unwrap();
+
+        let positions = run(&mut interpolator);
+        let (_, max_y) = *positions.iter().max_by_key(|(_, y)| *y).unwrap();
+        // The radius at a quarter turn is halfway between start and end.
+        assert!((125..=127).contains(&max_y));
+        assert_eq!(interpolator.position(), (-150, 0));
+    }
+
+    #[test]
+    fn interpolator_should_reject_invalid_motions() {
+        let mut interpolator = TestInterpolator::new(Trapezoidal::new(1000.0));
+
+        let result = interpolator.enter_arc(
+            (0.0, 0.0),
+            (100, 0),
+            Rotation::Clockwise,
+            500.0,
+        );
+        assert_eq!(result, Err(Error::ZeroRadius));
+
+        for &feed_rate in &[0.0, -500.0, f32::NAN] {
+            let result = interpolator.enter_line((100, 100), feed_rate);
+            assert_eq!(result, Err(Error::InvalidFeedRate));
+        }
+        assert!(interpolator.is_idle());
+
+        interpolator.enter_line((100, 100), 500.0).unwrap();
+        interpolator.next_step();
+        assert_eq!(interpolator.enter_line((0, 0), 500.0), Err(Error::NotIdle));
+        assert_eq!(interpolator.set_position((0, 0)), Err(Error::NotIdle));
+    }
+
+    #[test]
+    fn interpolator_should_work_with_fixed_point_numbers() {
+        let mut interpolator: Interpolator<Trapezoidal> = Interpolator::new(
+            Trapezoidal::new(fixed::types::U32F32::from_num(1000)),
+        );
+        interpolator.set_position((-300, 400)).unwrap();
+
+        let center = (fixed::types::I32F32::ZERO, fixed::types::I32F32::ZERO);
+        let velocity = fixed::types::U32F32::from_num(500);
+        interpolator
+            .enter_arc(center, (-300, 400), Rotation::Clockwise, velocity)
+            .unwrap();
+
+        let mut steps = 0;
+        while interpolator.next_step().is_some() {
+            let (x, y) = interpolator.position();
+            let radius = ((x * x + y * y) as f32).sqrt();
+            approx::assert_abs_diff_eq!(radius, 500.0, epsilon = 1.0);
+            steps += 1;
+        }
+
+        assert!(steps >= 4 * 500);
+        assert_eq!(interpolator.position(), (-300, 400));
+    }
+}
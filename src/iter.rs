@@ -9,6 +9,8 @@ use crate::MotionProfile;
 /// An iterator over delay values
 ///
 /// Can be created by calling [`MotionProfile::delays`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Delays<'r, Profile>(pub &'r mut Profile);
 
 impl<'r, Profile> Iterator for Delays<'r, Profile>
@@ -25,6 +27,8 @@ where
 /// An iterator over velocity values
 ///
 /// Can be created by calling [`MotionProfile::velocities`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Velocities<'r, Profile>(pub &'r mut Profile);
 
 impl<'r, Profile> Iterator for Velocities<'r, Profile>
@@ -42,6 +46,8 @@ where
 /// An iterator over acceleration values
 ///
 /// Can be created by calling [`MotionProfile::accelerations`].
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Accelerations<'r, Profile: MotionProfile, Accel> {
     /// The motion profile
     pub profile: &'r mut Profile,
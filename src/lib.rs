@@ -8,6 +8,40 @@
 //!
 //! - [`Flat`]: Not for serious use, but might be useful for testing.
 //! - [`Trapezoidal`]: Constant-acceleration motion profile.
+//! - [`ExactTrapezoidal`]: Constant-acceleration motion profile with exact step
+//!   times, at the cost of computing a square root per step.
+//! - [`Austin`]: Constant-acceleration motion profile that only needs a
+//!   division per step, suitable for small microcontrollers.
+//! - [`Sinusoidal`]: Motion profile with sinusoidal acceleration, which limits
+//!   the jerk.
+//! - [`Exponential`]: Motion profile whose velocity approaches the maximum
+//!   velocity exponentially, like many legacy stepper controllers do.
+//! - [`Pvt`]: Motion profile that follows a stream of position-velocity-time
+//!   points, as planned by a host computer.
+//! - [`Quintic`]: Minimum-jerk motion profile with continuous acceleration,
+//!   for short, precise moves.
+//! - [`TableProfile`]: Motion profile that follows a user-defined acceleration
+//!   curve from a lookup table.
+//!
+//! Besides the motion profiles, the following helpers build on top of any
+//! [`MotionProfile`]:
+//!
+//! - [`axis::Axis`]: Tracks the absolute position and enforces travel limits.
+//! - [`homing::Homing`]: Runs a homing sequence against a limit switch.
+//! - [`interpolation::Interpolator`]: Coordinates two axes along lines and
+//!   circular arcs, with the feed rate following a motion profile.
+//! - [`microstep::Microstepping`]: Switches the microstepping resolution
+//!   depending on the velocity.
+//! - [`pause::Pausable`]: Pauses and resumes motions, for a feed hold, for
+//!   example.
+//! - [`queue::Executor`]: Executes moves from a [`queue::Queue`] back to back,
+//!   optionally fed lock-free from another execution context.
+//!
+//! If the **`std`** feature is enabled, the `sim` module can be used to run
+//! motion profiles on the host and export the results as CSV or JSON.
+//!
+//! If the **`gcode`** feature is enabled, the `gcode` module interprets a
+//! subset of G-code, which can then be executed using the interpolator.
 //!
 //! Trinamic have [an overview over motion profiles][overview] on their website.
 //!
@@ -25,19 +59,56 @@
 //! - Enable the **`libm`** feature. This provides the require square root
 //!   support via [libm].
 //!
+//! The following features enable optional functionality:
+//! - **`serde`** implements `Serialize`/`Deserialize` from [serde] for the
+//!   motion profiles and their parameters. This works with `f32`/`f64`, as well
+//!   as the types from the `fixed` crate.
+//! - **`defmt`** implements `Format` from [defmt] for the motion profiles,
+//!   iterators, and any debugging information they provide.
+//! - **`gcode`** enables the `gcode` module, which interprets G-code for
+//!   plotters and similar machines. This works without the standard library.
+//! - **`cli`** builds the `ramp-maker-cli` command-line tool, which can be
+//!   used to generate, inspect, and plot ramps without writing any Rust code.
+//!   Run `cargo run --features cli -- --help` for more information.
+//!
 //! [Stepper]: https://crates.io/crates/stepper
 //! [overview]: https://www.trinamic.com/technology/motion-control-technology/
 //! [libm]: https://crates.io/crates/libm
+//! [serde]: https://crates.io/crates/serde
+//! [defmt]: https://crates.io/crates/defmt
 
 #![cfg_attr(all(not(test), not(feature = "std")), no_std)]
 #![deny(missing_docs, rustdoc::broken_intra_doc_links)]
 
+pub mod austin;
+pub mod axis;
+pub mod exact_trapezoidal;
+pub mod exponential;
 pub mod flat;
+pub mod homing;
+pub mod interpolation;
 pub mod iter;
+pub mod microstep;
+pub mod pause;
+pub mod pvt;
+pub mod queue;
+pub mod quintic;
+pub mod sinusoidal;
+pub mod table;
 pub mod trapezoidal;
 pub mod util;
 
-pub use self::{flat::Flat, trapezoidal::Trapezoidal};
+#[cfg(any(test, feature = "std"))]
+pub mod sim;
+
+#[cfg(any(test, feature = "gcode"))]
+pub mod gcode;
+
+pub use self::{
+    austin::Austin, exact_trapezoidal::ExactTrapezoidal,
+    exponential::Exponential, flat::Flat, pvt::Pvt, quintic::Quintic,
+    sinusoidal::Sinusoidal, table::TableProfile, trapezoidal::Trapezoidal,
+};
 
 /// Abstract interface for motion profiles
 ///
@@ -85,11 +156,39 @@ pub trait MotionProfile: Sized {
     /// method.
     fn next_delay(&mut self) -> Option<Self::Delay>;
 
+    /// Return the number of steps left until the motion reaches its target
+    ///
+    /// This is the number of steps passed to
+    /// [`MotionProfile::enter_position_mode`], minus the number of steps made
+    /// since.
+    fn steps_remaining(&self) -> u32;
+
+    /// Return the current velocity
+    ///
+    /// This is the velocity of the most recent step, or zero, if the motion
+    /// profile is idle, or hasn't made a step since it started moving from a
+    /// stand-still.
+    fn current_velocity(&self) -> Self::Velocity;
+
+    /// Indicate whether the motion profile is idle
+    ///
+    /// If this returns `true`, the next call to [`MotionProfile::next_delay`]
+    /// is going to return `None`.
+    fn is_idle(&self) -> bool;
+
+    /// Return the number of steps needed to come to a stop
+    ///
+    /// This can be compared to the distance to a new target, to determine
+    /// whether it can be reached without overshooting it. The result might be
+    /// rounded up by a step, but is never lower than the actual number of
+    /// steps. Zero, if the motion profile is idle.
+    fn steps_to_stop(&self) -> u32;
+
     /// Return an iterator over delay values of each step
     ///
     /// This is a convenience method that returns an iterator which internally
     /// just calls [`MotionProfile::next_delay`].
-    fn delays(&mut self) -> iter::Delays<Self> {
+    fn delays(&mut self) -> iter::Delays<'_, Self> {
         iter::Delays(self)
     }
 
@@ -100,7 +199,7 @@ pub trait MotionProfile: Sized {
     /// velocity.
     ///
     /// This is mainly useful for testing and debugging.
-    fn velocities(&mut self) -> iter::Velocities<Self> {
+    fn velocities(&mut self) -> iter::Velocities<'_, Self> {
         iter::Velocities(self)
     }
 
@@ -111,7 +210,67 @@ pub trait MotionProfile: Sized {
     /// each pair of delay values.
     ///
     /// This is mainly useful for testing and debugging.
-    fn accelerations<Accel>(&mut self) -> iter::Accelerations<Self, Accel> {
+    fn accelerations<Accel>(&mut self) -> iter::Accelerations<'_, Self, Accel> {
         iter::Accelerations::new(self)
     }
 }
+
+/// The direction of a step
+///
+/// Motion profiles only ever deal with the number of steps, not with their
+/// direction. This type is used by the helpers in this library that need to
+/// keep track of the direction of the motor, like [`axis::Axis`] and
+/// [`homing::Homing`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    /// Step in the positive direction
+    Forward,
+
+    /// Step in the negative direction
+    Backward,
+}
+
+impl Direction {
+    /// Return the opposite direction
+    pub fn reverse(self) -> Self {
+        match self {
+            Self::Forward => Self::Backward,
+            Self::Backward => Self::Forward,
+        }
+    }
+
+    /// Return the change of position that a step in this direction causes
+    pub fn sign(self) -> i32 {
+        match self {
+            Self::Forward => 1,
+            Self::Backward => -1,
+        }
+    }
+}
+
+/// Error returned when starting a motion that must take a given time
+///
+/// Returned by [`Trapezoidal::enter_timed_position_mode`] and
+/// [`Quintic::enter_timed_position_mode`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimedMoveError {
+    /// A motion is in progress
+    ///
+    /// A timed motion must start from a stand-still.
+    NotIdle,
+
+    /// The motion can't be made in the given time
+    ///
+    /// It would require exceeding the acceleration or the maximum velocity.
+    DurationTooShort,
+
+    /// The motion can't be made in the given time
+    ///
+    /// It would require an acceleration too low to be represented by the
+    /// numeric type. Only returned by [`Trapezoidal`], which lowers its
+    /// acceleration for long durations.
+    DurationTooLong,
+}
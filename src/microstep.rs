@@ -0,0 +1,414 @@
+//! Switching of the microstepping resolution during a motion
+//!
+//! See [`Microstepping`].
+
+use core::ops::Add;
+
+use crate::{Direction, MotionProfile};
+
+/// Switches between microstepping resolutions, depending on the velocity
+///
+/// At high velocities, the step rate needed for fine microstepping can exceed
+/// what the hardware is able to generate. This struct wraps a
+/// [`MotionProfile`] and switches the driver to a coarser resolution, once the
+/// velocity exceeds [`Config::upper_threshold`], and back to the fine
+/// resolution, once it drops below [`Config::lower_threshold`].
+///
+/// Create an instance of this struct using [`Microstepping::new`], start a
+/// motion using [`Microstepping::enter_position_mode`], then call
+/// [`Microstepping::next_event`] to get the steps and resolution changes.
+///
+/// # Units
+///
+/// The wrapped motion profile always works in fine microsteps. All velocities
+/// and step counts passed to this struct are in fine microsteps, too. While
+/// the coarse resolution is active, the delays of the fine microsteps that
+/// make up a coarse step are added up, which keeps the physical motion
+/// continuous.
+///
+/// # Alignment
+///
+/// The resolution is only switched to the coarse one at full-step positions.
+/// It is assumed that the motor is at a full-step position, when this struct
+/// is created. Switching back to the fine resolution is possible at any
+/// position reached in the coarse resolution. Before the end of a motion, the
+/// resolution is always switched back to the fine one, as the remaining steps
+/// might not add up to a coarse step.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Microstepping<Profile: MotionProfile> {
+    profile: Profile,
+    config: Config<Profile::Velocity>,
+    direction: Direction,
+    phase: u16,
+    coarse: bool,
+}
+
+impl<Profile> Microstepping<Profile>
+where
+    Profile: MotionProfile,
+    Profile::Velocity: Copy + PartialOrd,
+    Profile::Delay: Add<Output = Profile::Delay>,
+{
+    /// Create a new instance of `Microstepping`
+    ///
+    /// Starts out with the fine resolution.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the coarse resolution is zero, exceeds the fine resolution,
+    /// or doesn't divide it, or if the lower threshold is larger than the
+    /// upper one.
+    pub fn new(profile: Profile, config: Config<Profile::Velocity>) -> Self {
+        assert!(
+            config.coarse != 0
+                && config.coarse <= config.fine
+                && config.fine % config.coarse == 0,
+            "Coarse resolution must divide fine resolution"
+        );
+        assert!(
+            config.lower_threshold <= config.upper_threshold,
+            "Lower threshold must not exceed upper threshold"
+        );
+
+        Self {
+            profile,
+            config,
+            direction: Direction::Forward,
+            phase: 0,
+            coarse: false,
+        }
+    }
+
+    /// Start a motion of `num_steps` fine microsteps in the given direction
+    ///
+    /// See [`MotionProfile::enter_position_mode`].
+    ///
+    /// Returns an error, if the direction differs from that of a motion in
+    /// progress. Stop the motion first, by passing a target of zero steps in
+    /// the current direction, and change the direction once it has ended.
+    pub fn enter_position_mode(
+        &mut self,
+        direction: Direction,
+        max_velocity: Profile::Velocity,
+        num_steps: u32,
+    ) -> Result<(), Error> {
+        if direction != self.direction && !self.profile.is_idle() {
+            return Err(Error::NotIdle);
+        }
+
+        self.direction = direction;
+        self.profile.enter_position_mode(max_velocity, num_steps);
+
+        Ok(())
+    }
+
+    /// Return the next event
+    ///
+    /// Returns `None`, if the motion has ended.
+    pub fn next_event(&mut self) -> Option<Event<Profile::Delay>> {
+        let velocity = self.profile.current_velocity();
+        let ratio = u32::from(self.config.fine / self.config.coarse);
+        let enough_steps = self.profile.steps_remaining() >= ratio;
+
+        if self.coarse {
+            if velocity < self.config.lower_threshold || !enough_steps {
+                self.coarse = false;
+                return Some(Event::ChangeResolution(self.config.fine));
+            }
+
+            let mut delay = self.next_microstep()?;
+            for _ in 1..ratio {
+                match self.next_microstep() {
+                    Some(next) => delay = delay + next,
+                    None => break,
+                }
+            }
+
+            return Some(Event::Step(delay));
+        }
+
+        let aligned = self.phase == 0;
+        if aligned && enough_steps && velocity >= self.config.upper_threshold {
+            self.coarse = true;
+            return Some(Event::ChangeResolution(self.config.coarse));
+        }
+
+        self.next_microstep().map(Event::Step)
+    }
+
+    /// Return the number of steps left, in the current resolution
+    ///
+    /// While the coarse resolution is active, this doesn't include the steps
+    /// that don't add up to a full coarse step, and will be made in the fine
+    /// resolution.
+    pub fn steps_remaining(&self) -> u32 {
+        let steps = self.profile.steps_remaining();
+        if self.coarse {
+            steps / u32::from(self.config.fine / self.config.coarse)
+        } else {
+            steps
+        }
+    }
+
+    /// Return the current resolution in microsteps per full step
+    pub fn resolution(&self) -> u16 {
+        if self.coarse {
+            self.config.coarse
+        } else {
+            self.config.fine
+        }
+    }
+
+    /// Release the motion profile
+    pub fn into_profile(self) -> Profile {
+        self.profile
+    }
+
+    fn next_microstep(&mut self) -> Option<Profile::Delay> {
+        let delay = self.profile.next_delay()?;
+
+        let last = self.config.fine - 1;
+        self.phase = match self.direction {
+            Direction::Forward if self.phase == last => 0,
+            Direction::Forward => self.phase + 1,
+            Direction::Backward if self.phase == 0 => last,
+            Direction::Backward => self.phase - 1,
+        };
+
+        Some(delay)
+    }
+}
+
+/// The configuration of [`Microstepping`]
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Config<Velocity> {
+    /// The fine resolution in microsteps per full step, used at low velocities
+    pub fine: u16,
+
+    /// The coarse resolution in microsteps per full step, used at high
+    /// velocities
+    ///
+    /// Must divide the fine resolution.
+    pub coarse: u16,
+
+    /// Switch to the coarse resolution at or above this velocity
+    ///
+    /// In fine microsteps per unit of time.
+    pub upper_threshold: Velocity,
+
+    /// Switch back to the fine resolution below this velocity
+    ///
+    /// In fine microsteps per unit of time. Should be lower than the upper
+    /// threshold, to prevent switching back and forth at velocities close to
+    /// the thresholds.
+    pub lower_threshold: Velocity,
+}
+
+/// Error returned by [`Microstepping::enter_position_mode`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// A motion in the other direction is in progress
+    ///
+    /// The direction can only change, once the motion has ended.
+    NotIdle,
+}
+
+/// An event produced by [`Microstepping`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event<Delay> {
+    /// Make a step in the current resolution, after the given delay
+    Step(Delay),
+
+    /// Switch the driver to the given resolution, in microsteps per full step
+    ChangeResolution(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Direction, MotionProfile as _, Trapezoidal};
+
+    use super::{Config, Error, Event, Microstepping};
+
+    fn config() -> Config<f32> {
+        Config {
+            fine: 16,
+            coarse: 1,
+            upper_threshold: 8000.0,
+            lower_threshold: 6000.0,
+        }
+    }
+
+    /// Returns the events, and the position in fine microsteps before each
+    fn run(
+        microstepping: &mut Microstepping<Trapezoidal<f32>>,
+        mut position: i32,
+    ) -> Vec<(i32, Event<f32>)> {
+        let mut events = Vec::new();
+        while let Some(event) = microstepping.next_event() {
+            events.push((position, event));
+            if let Event::Step(_) = event {
+                let step = 16 / i32::from(microstepping.resolution());
+                position += step * microstepping.direction.sign();
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn microstepping_should_switch_resolution_at_full_steps() {
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config());
+        microstepping
+            .enter_position_mode(Direction::Forward, 16_000.0, 16_000)
+            .unwrap();
+
+        let events = run(&mut microstepping, 0);
+
+        let changes: Vec<_> = events
+            .iter()
+            .filter_map(|&(position, event)| match event {
+                Event::ChangeResolution(resolution) => {
+                    Some((position, resolution))
+                }
+                Event::Step(_) => None,
+            })
+            .collect();
+
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].1, 1);
+        assert_eq!(changes[0].0 % 16, 0);
+        assert_eq!(changes[1].1, 16);
+        assert_eq!(microstepping.resolution(), 16);
+    }
+
+    #[test]
+    fn microstepping_should_keep_motion_continuous() {
+        let mut profile = Trapezoidal::new(100_000.0);
+        profile.enter_position_mode(16_000.0, 16_000);
+        let expected: f32 = profile.delays().sum();
+
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config());
+        microstepping
+            .enter_position_mode(Direction::Forward, 16_000.0, 16_000)
+            .unwrap();
+
+        let mut microsteps = 0;
+        let mut full_steps = 0;
+        let mut duration = 0.0;
+        while let Some(event) = microstepping.next_event() {
+            if let Event::Step(delay) = event {
+                match microstepping.resolution() {
+                    16 => microsteps += 1,
+                    _ => full_steps += 1,
+                }
+                duration += delay;
+            }
+        }
+
+        assert!(full_steps > 0);
+        assert_eq!(microsteps + 16 * full_steps, 16_000);
+        approx::assert_abs_diff_eq!(duration, expected, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn microstepping_should_not_switch_between_thresholds() {
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config());
+        microstepping
+            .enter_position_mode(Direction::Forward, 7000.0, 16_000)
+            .unwrap();
+
+        let events = run(&mut microstepping, 0);
+        assert!(events
+            .iter()
+            .all(|(_, event)| matches!(event, Event::Step(_))));
+    }
+
+    #[test]
+    fn microstepping_should_track_phase_in_both_directions() {
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config());
+
+        microstepping
+            .enter_position_mode(Direction::Backward, 1000.0, 5)
+            .unwrap();
+        let position = -(run(&mut microstepping, 0).len() as i32);
+        assert_eq!(position, -5);
+
+        microstepping
+            .enter_position_mode(Direction::Forward, 16_000.0, 16_000)
+            .unwrap();
+        let events = run(&mut microstepping, position);
+
+        let (switch_position, _) = events
+            .iter()
+            .find(|(_, event)| *event == Event::ChangeResolution(1))
+            .unwrap();
+        assert_eq!(switch_position.rem_euclid(16), 0);
+    }
+
+    #[test]
+    fn microstepping_should_report_remaining_steps_in_current_resolution() {
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config());
+        microstepping
+            .enter_position_mode(Direction::Forward, 16_000.0, 16_000)
+            .unwrap();
+
+        while microstepping.resolution() == 16 {
+            microstepping.next_event();
+        }
+
+        let full_steps = microstepping.steps_remaining();
+        let microsteps = microstepping.into_profile().steps_remaining();
+        assert!(full_steps > 0);
+        assert_eq!(full_steps, microsteps / 16);
+    }
+
+    #[test]
+    fn microstepping_should_reject_direction_change_while_moving() {
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config());
+        microstepping
+            .enter_position_mode(Direction::Forward, 1000.0, 100)
+            .unwrap();
+        microstepping.next_event().unwrap();
+
+        assert_eq!(
+            microstepping.enter_position_mode(Direction::Backward, 1000.0, 100),
+            Err(Error::NotIdle),
+        );
+        assert_eq!(run(&mut microstepping, 0).len(), 99);
+
+        assert_eq!(
+            microstepping.enter_position_mode(Direction::Backward, 1000.0, 100),
+            Ok(()),
+        );
+    }
+
+    #[test]
+    fn microstepping_should_not_overflow_phase_at_high_resolution() {
+        let config = Config {
+            fine: u16::MAX,
+            ..config()
+        };
+
+        let mut microstepping =
+            Microstepping::new(Trapezoidal::new(100_000.0), config);
+        microstepping
+            .enter_position_mode(Direction::Backward, 1000.0, 3)
+            .unwrap();
+
+        assert_eq!(run(&mut microstepping, 0).len(), 3);
+        assert_eq!(microstepping.phase, u16::MAX - 3);
+    }
+}
@@ -0,0 +1,201 @@
+//! Pausing and resuming motions
+//!
+//! See [`Pausable`].
+
+use crate::MotionProfile;
+
+/// Wraps a [`MotionProfile`], adding the ability to pause and resume motions
+///
+/// Pausing a motion decelerates to a stop, using the ramp of the wrapped motion
+/// profile, but remembers how many steps are left to the target step. Resuming
+/// accelerates back to the maximum velocity that was last passed to
+/// [`MotionProfile::enter_position_mode`], and finishes the motion on the
+/// original target step. This is useful to implement a feed hold, for example.
+///
+/// Create an instance of this struct using [`Pausable::new`], then use the API
+/// defined by [`MotionProfile`] (which this struct implements) to start a
+/// motion, and [`Pausable::pause`] and [`Pausable::resume`] to pause and resume
+/// it.
+///
+/// # Paused Motions
+///
+/// Once a paused motion has come to a stop, [`MotionProfile::next_delay`]
+/// returns `None`, but [`MotionProfile::is_idle`] returns `false`, and
+/// [`MotionProfile::steps_remaining`] still returns the number of steps left
+/// to the target step. Calling [`MotionProfile::enter_position_mode`] replaces
+/// the paused motion.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pausable<Profile: MotionProfile> {
+    profile: Profile,
+    max_velocity: Option<Profile::Velocity>,
+
+    /// The number of steps left to the target step, if paused
+    paused: Option<u32>,
+}
+
+impl<Profile> Pausable<Profile>
+where
+    Profile: MotionProfile,
+    Profile::Velocity: Copy,
+{
+    /// Create a new instance of `Pausable`
+    pub fn new(profile: Profile) -> Self {
+        Self {
+            profile,
+            max_velocity: None,
+            paused: None,
+        }
+    }
+
+    /// Pause the current motion
+    ///
+    /// Decelerates to a stop, but remembers how many steps are left to the
+    /// target step. Call [`Pausable::resume`] to finish the motion.
+    ///
+    /// The steps made while decelerating still count towards the target step.
+    /// If the target step is too close for a full stop, the motion ends there,
+    /// just like it would have without pausing, and the motion isn't paused.
+    pub fn pause(&mut self) {
+        if self.paused.is_some() || self.profile.is_idle() {
+            return;
+        }
+        let max_velocity = match self.max_velocity {
+            Some(max_velocity) => max_velocity,
+            None => return,
+        };
+
+        let steps_left = self.profile.steps_remaining();
+        if self.profile.steps_to_stop() >= steps_left {
+            return;
+        }
+
+        self.profile.enter_position_mode(max_velocity, 0);
+        self.paused = Some(steps_left);
+    }
+
+    /// Resume a paused motion
+    ///
+    /// Accelerates back to the maximum velocity that was last passed to
+    /// [`MotionProfile::enter_position_mode`], and finishes the motion on the
+    /// original target step. Does nothing, if the motion is not paused.
+    pub fn resume(&mut self) {
+        if let (Some(steps_left), Some(max_velocity)) =
+            (self.paused.take(), self.max_velocity)
+        {
+            self.profile.enter_position_mode(max_velocity, steps_left);
+        }
+    }
+
+    /// Indicate whether the motion is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Access the motion profile
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Release the motion profile
+    pub fn into_profile(self) -> Profile {
+        self.profile
+    }
+}
+
+impl<Profile> MotionProfile for Pausable<Profile>
+where
+    Profile: MotionProfile,
+    Profile::Velocity: Copy,
+{
+    type Velocity = Profile::Velocity;
+    type Delay = Profile::Delay;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        self.max_velocity = Some(max_velocity);
+        self.paused = None;
+        self.profile.enter_position_mode(max_velocity, num_steps);
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        let delay = self.profile.next_delay()?;
+        if let Some(steps_left) = &mut self.paused {
+            *steps_left = steps_left.saturating_sub(1);
+        }
+        Some(delay)
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        match self.paused {
+            Some(steps_left) => steps_left,
+            None => self.profile.steps_remaining(),
+        }
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        self.profile.current_velocity()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.profile.is_idle() && self.paused.is_none()
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        self.profile.steps_to_stop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MotionProfile as _, Trapezoidal};
+
+    use super::Pausable;
+
+    #[test]
+    fn pausable_should_resume_at_previous_max_velocity() {
+        let mut profile = Pausable::new(Trapezoidal::<f32>::new(6000.0));
+
+        let num_steps = 500;
+        profile.enter_position_mode(1000.0, num_steps);
+        let max_velocity = profile.velocities().take(200).fold(0.0, f32::max);
+
+        profile.pause();
+        let mut steps_while_pausing = 0;
+        let mut prev_velocity = None;
+        for velocity in profile.velocities() {
+            if let Some(prev_velocity) = prev_velocity {
+                assert!(velocity < prev_velocity);
+            }
+            prev_velocity = Some(velocity);
+            steps_while_pausing += 1;
+        }
+        assert!(steps_while_pausing > 1);
+
+        profile.resume();
+        let velocities: Vec<f32> = profile.velocities().collect();
+
+        let resumed_velocity = velocities.iter().copied().fold(0.0, f32::max);
+        assert_eq!(resumed_velocity, max_velocity);
+        assert_eq!(
+            200 + steps_while_pausing + velocities.len() as u32,
+            num_steps,
+        );
+    }
+
+    #[test]
+    fn pausable_should_not_pause_if_target_is_too_close() {
+        let mut profile = Pausable::new(Trapezoidal::<f32>::new(6000.0));
+
+        profile.enter_position_mode(1000.0, 100);
+        profile.delays().take(90).for_each(drop);
+
+        profile.pause();
+        assert!(!profile.is_paused());
+        assert_eq!(profile.delays().count(), 10);
+        assert!(profile.is_idle());
+    }
+}
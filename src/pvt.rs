@@ -0,0 +1,926 @@
+//! Position-velocity-time (PVT) motion profile
+//!
+//! See [`Pvt`].
+
+use core::{convert::TryFrom as _, ops};
+
+use az::Az as _;
+
+use crate::{
+    util::{sub, traits::Sqrt},
+    Direction, MotionProfile,
+};
+
+/// Position-velocity-time (PVT) motion profile
+///
+/// Follows a trajectory that is defined by a stream of [`Point`]s, each of
+/// which consists of an absolute position, a velocity, and a timestamp. This
+/// allows a host computer to plan arbitrary trajectories, and stream them to a
+/// microcontroller, which uses this struct to generate the steps.
+///
+/// Between two points, the position is interpolated using a cubic Hermite
+/// polynomial, which matches the position and velocity of both points. Each
+/// step is made at the time the interpolated position reaches the next step
+/// position. Those times are computed using Newton's method, safeguarded by
+/// bisection.
+///
+/// Create an instance of this struct using [`Pvt::new`], then add points using
+/// [`Pvt::push`]. The first point defines where the trajectory starts. Call
+/// [`Pvt::next_step`] to get the direction and delay of each step.
+///
+/// # Buffer
+///
+/// The points are stored in a buffer with a capacity of `N` points. A point is
+/// removed from the buffer once the motion has passed it. If the buffer runs
+/// empty, the motion ends at the last point, no matter its velocity. Keep the
+/// buffer filled to prevent that.
+///
+/// Points that are pushed after the motion has ended continue from the last
+/// point. Their timestamps must continue from its timestamp, as any gap
+/// between the timestamps is interpreted as part of the motion.
+///
+/// # Motion Profile
+///
+/// This struct implements [`MotionProfile`]. As the trajectory can change
+/// direction, [`MotionProfile::next_delay`] is only suitable for trajectories
+/// that don't. [`MotionProfile::enter_position_mode`] replaces the buffered
+/// trajectory with a segment from the current position and velocity to a
+/// point the given number of steps ahead, which is reached at zero velocity,
+/// in the time that keeps the velocity within the maximum velocity.
+///
+/// There is no acceleration limit to decelerate with, so a trajectory can't be
+/// cut short. [`MotionProfile::steps_to_stop`] returns the number of steps
+/// until the end of the buffered trajectory, and a target closer than that
+/// leaves the trajectory unchanged.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent
+/// velocities, timestamps, and delays. As velocities can be negative, it must
+/// be a signed type. It is set to a 64-bit fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other signed type
+/// from the `fixed` crate. Please note that you need to enable support for
+/// `f32`/`f64` explicitly. Check out the section on Cargo features from the
+/// documentation in the root module for more information.
+///
+/// # Serialization
+///
+/// Unlike most other types in this library, this struct doesn't support
+/// serialization via serde, as serde can't handle arrays of arbitrary size.
+/// [`Point`] supports it, however.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Pvt<Num = DefaultNum, const N: usize = 16> {
+    /// Ring buffer of points that haven't been reached yet
+    buffer: [Option<Point<Num>>; N],
+    head: usize,
+    len: usize,
+
+    /// The last point that has been reached
+    from: Option<Point<Num>>,
+
+    /// The normalized time within the segment that starts at `from`
+    tau: Num,
+
+    position: i32,
+    time_prev: Num,
+    delay_prev: Option<Num>,
+
+    /// The target of the last call to `enter_position_mode`
+    target: Option<i32>,
+}
+
+impl<Num, const N: usize> Pvt<Num, N>
+where
+    Num: Copy + num_traits::Zero,
+{
+    /// Create a new instance of `Pvt`
+    pub fn new() -> Self {
+        Self {
+            buffer: [None; N],
+            head: 0,
+            len: 0,
+
+            from: None,
+            tau: Num::zero(),
+
+            position: 0,
+            time_prev: Num::zero(),
+            delay_prev: None,
+
+            target: None,
+        }
+    }
+}
+
+impl<Num, const N: usize> Pvt<Num, N>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Sqrt,
+    i32: az::Cast<Num>,
+{
+    /// Add a point to the end of the trajectory
+    ///
+    /// Returns the point as an error, if the buffer is full, or if its
+    /// timestamp is not later than that of the previous point.
+    pub fn push(&mut self, point: Point<Num>) -> Result<(), Point<Num>> {
+        if self.len == N {
+            return Err(point);
+        }
+        if let Some(last) = self.last_point() {
+            if point.time <= last.time {
+                return Err(point);
+            }
+        }
+
+        self.buffer[(self.head + self.len) % N] = Some(point);
+        self.len += 1;
+        self.target = None;
+
+        Ok(())
+    }
+
+    /// Return the number of points that can be pushed before the buffer is
+    /// full
+    pub fn free_capacity(&self) -> usize {
+        N - self.len
+    }
+
+    /// Return the current absolute position
+    pub fn position(&self) -> i32 {
+        self.position
+    }
+
+    /// Return the direction and delay of the next step
+    ///
+    /// Returns `None`, if the motion has reached the last point in the buffer.
+    pub fn next_step(&mut self) -> Option<(Direction, Num)> {
+        if self.from.is_none() {
+            let first = self.pop()?;
+            self.from = Some(first);
+            self.tau = Num::zero();
+            self.position = first.position;
+            self.time_prev = first.time;
+        }
+
+        loop {
+            let from = self.from?;
+            let to = self.peek()?;
+            let segment = Segment::new(&from, &to);
+
+            let k = (self.position - from.position).az::<Num>();
+            if let Some((direction, tau)) = segment.next_crossing(self.tau, k) {
+                let time = from.time + tau * segment.duration;
+                let delay = sub(time, self.time_prev);
+
+                self.tau = tau;
+                self.position += direction.sign();
+                self.time_prev = time;
+                self.delay_prev = Some(delay);
+
+                if tau >= Num::one() {
+                    self.advance();
+                }
+
+                return Some((direction, delay));
+            }
+
+            // No more steps in this segment. Move on to the next one.
+            self.advance();
+        }
+    }
+
+    fn advance(&mut self) {
+        self.from = self.pop();
+        self.tau = Num::zero();
+    }
+
+    fn peek(&self) -> Option<Point<Num>> {
+        if self.len == 0 {
+            return None;
+        }
+        self.buffer[self.head]
+    }
+
+    fn pop(&mut self) -> Option<Point<Num>> {
+        let point = self.peek()?;
+        self.buffer[self.head] = None;
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(point)
+    }
+
+    fn last_point(&self) -> Option<Point<Num>> {
+        if self.len == 0 {
+            return self.from;
+        }
+        self.buffer[(self.head + self.len - 1) % N]
+    }
+
+    /// Remove all points from the buffer
+    fn clear(&mut self) {
+        self.buffer = [None; N];
+        self.head = 0;
+        self.len = 0;
+        self.from = None;
+        self.tau = Num::zero();
+    }
+
+    /// Return the velocity of the trajectory at the current position
+    fn segment_velocity(&self) -> Option<Num> {
+        let from = self.from?;
+        let to = self.segment_end()?;
+        let segment = Segment::new(&from, &to);
+        Some(segment.slope(self.tau) / segment.duration)
+    }
+
+    /// Return the end point of the current segment
+    fn segment_end(&self) -> Option<Point<Num>> {
+        let index = match self.from {
+            Some(_) => 0,
+            None => 1,
+        };
+        if index >= self.len {
+            return None;
+        }
+        self.buffer[(self.head + index) % N]
+    }
+}
+
+impl<Num, const N: usize> Default for Pvt<Num, N>
+where
+    Num: Copy + num_traits::Zero,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Num, const N: usize> MotionProfile for Pvt<Num, N>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Sqrt
+        + az::Cast<u32>,
+    i32: az::Cast<Num>,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    /// Move `num_steps` steps ahead of the current position
+    ///
+    /// Replaces the buffered trajectory with a single segment, which starts at
+    /// the current position and velocity, and ends at the target at zero
+    /// velocity. If the target is closer than [`MotionProfile::steps_to_stop`],
+    /// the buffered trajectory is left as it is, as it can't be cut short.
+    ///
+    /// Positions are `i32`, so the target is clamped to `i32::MAX`.
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        let num_steps = if max_velocity.is_zero() { 0 } else { num_steps };
+
+        let num_steps = i32::try_from(num_steps).unwrap_or(i32::MAX);
+        let target = self.position.saturating_add(num_steps);
+        let num_steps = (target - self.position).unsigned_abs();
+
+        let steps_to_stop = self.steps_to_stop();
+        self.target = Some(target);
+
+        if num_steps == 0 || num_steps < steps_to_stop || N < 2 {
+            return;
+        }
+
+        // Moving backwards isn't supported here, so we start from a
+        // stand-still in that case.
+        let velocity = self.segment_velocity().unwrap_or(Num::zero());
+        let velocity = if velocity > Num::zero() {
+            velocity
+        } else {
+            Num::zero()
+        };
+
+        // A cubic segment that starts and ends at zero velocity reaches its
+        // peak velocity of `3D / 2T` in the middle. Starting at a higher
+        // velocity than zero, but not higher than the maximum, only lowers
+        // the peak. If the initial velocity exceeds the maximum, we need to
+        // slow down gradually instead, and starting at more than `3D / T`
+        // would overshoot the target.
+        let two = Num::one() + Num::one();
+        let three = two + Num::one();
+        let distance = num_steps.az::<Num>();
+        let duration = three * distance / (two * max_velocity);
+        let duration = if velocity * duration > three * distance {
+            three * distance / velocity
+        } else {
+            duration
+        };
+
+        self.clear();
+
+        // Can't fail, as the buffer is empty and the timestamps increase.
+        let _ = self.push(Point {
+            position: self.position,
+            velocity,
+            time: self.time_prev,
+        });
+        let _ = self.push(Point {
+            position: target,
+            velocity: Num::zero(),
+            time: self.time_prev + duration,
+        });
+        self.target = Some(target);
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        self.next_step().map(|(_, delay)| delay)
+    }
+
+    /// Return the number of steps remaining
+    ///
+    /// This is the distance to the target of the last call to
+    /// [`MotionProfile::enter_position_mode`], or to the last point in the
+    /// buffer, if points have been pushed since.
+    fn steps_remaining(&self) -> u32 {
+        if let Some(target) = self.target {
+            return target.wrapping_sub(self.position).max(0).unsigned_abs();
+        }
+
+        match self.last_point() {
+            Some(last) => {
+                last.position.wrapping_sub(self.position).unsigned_abs()
+            }
+            None => 0,
+        }
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        if self.is_idle() {
+            return Num::zero();
+        }
+
+        match self.delay_prev {
+            Some(delay_prev) if !delay_prev.is_zero() => delay_prev.inv(),
+            // Two steps at the same time are possible due to rounding errors.
+            // Fall back to the velocity of the trajectory.
+            _ => {
+                let velocity = self.segment_velocity().unwrap_or(Num::zero());
+                if velocity < Num::zero() {
+                    Num::zero() - velocity
+                } else {
+                    velocity
+                }
+            }
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.segment_end().is_none()
+    }
+
+    /// Return the number of steps until the end of the trajectory
+    ///
+    /// The trajectory can't be cut short, but this accounts for any steps
+    /// that overshoot a point and come back.
+    fn steps_to_stop(&self) -> u32 {
+        let mut from = match self.from {
+            Some(from) => from,
+            None => return 0,
+        };
+
+        let mut variation = Num::zero();
+        let mut tau = self.tau;
+
+        // The motor might already be ahead of or behind the trajectory, due
+        // to the hysteresis of the steps.
+        if let Some(to) = self.segment_end() {
+            let segment = Segment::new(&from, &to);
+            let k = (self.position - from.position).az::<Num>();
+            variation = abs(segment.position(tau) - k);
+        }
+
+        for i in 0..self.len {
+            let to = match self.buffer[(self.head + i) % N] {
+                Some(to) => to,
+                None => break,
+            };
+
+            variation = variation + Segment::new(&from, &to).variation(tau);
+            from = to;
+            tau = Num::zero();
+        }
+
+        // Round to the nearest step, to be safe from rounding errors. This
+        // might count one step too many, but never too few.
+        let half = Num::one() / (Num::one() + Num::one());
+        (variation + half).az::<u32>()
+    }
+}
+
+/// The default numeric type used by [`Pvt`]
+pub type DefaultNum = fixed::FixedI64<typenum::U32>;
+
+/// A point of a PVT trajectory
+///
+/// Passed to [`Pvt::push`].
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Point<Num = DefaultNum> {
+    /// The absolute position in steps
+    pub position: i32,
+
+    /// The velocity in steps per unit of time
+    ///
+    /// Negative velocities move towards lower positions.
+    pub velocity: Num,
+
+    /// The timestamp
+    ///
+    /// Only the differences between timestamps matter, so any reference point
+    /// can be used, as long as the timestamps increase from point to point.
+    pub time: Num,
+}
+
+/// A segment between two points
+///
+/// The position relative to the start point is `a3 * τ³ + a2 * τ² + a1 * τ`,
+/// where `τ` is the normalized time, which goes from 0 to 1 over the duration
+/// of the segment.
+struct Segment<Num> {
+    duration: Num,
+    distance: Num,
+    a3: Num,
+    a2: Num,
+    a1: Num,
+}
+
+impl<Num> Segment<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Sqrt,
+    i32: az::Cast<Num>,
+{
+    fn new(from: &Point<Num>, to: &Point<Num>) -> Self {
+        let two = Num::one() + Num::one();
+        let three = two + Num::one();
+
+        let duration = to.time - from.time;
+        let distance = to.position.wrapping_sub(from.position).az::<Num>();
+
+        // The velocities, scaled to normalized time.
+        let m0 = from.velocity * duration;
+        let m1 = to.velocity * duration;
+
+        Self {
+            duration,
+            distance,
+            a3: m0 + m1 - two * distance,
+            a2: three * distance - two * m0 - m1,
+            a1: m0,
+        }
+    }
+
+    fn position(&self, tau: Num) -> Num {
+        if tau >= Num::one() {
+            // Exact, regardless of rounding errors.
+            return self.distance;
+        }
+        ((self.a3 * tau + self.a2) * tau + self.a1) * tau
+    }
+
+    fn slope(&self, tau: Num) -> Num {
+        let two = Num::one() + Num::one();
+        let three = two + Num::one();
+        (three * self.a3 * tau + two * self.a2) * tau + self.a1
+    }
+
+    /// Find the next time at which a step needs to be made
+    ///
+    /// `k` is the position of the motor relative to the start of the segment.
+    /// A step forward is made when the interpolated position reaches `k + 1`, a
+    /// step backward when it reaches `k - 1`. This hysteresis prevents
+    /// stepping back and forth, if the trajectory turns around close to a step
+    /// position.
+    fn next_crossing(&self, tau: Num, k: Num) -> Option<(Direction, Num)> {
+        let one = Num::one();
+
+        // Split the rest of the segment into intervals in which the position is
+        // monotonic.
+        let (c1, c2) = self.critical_points();
+        let mut bounds = [tau, one, one, one];
+        let mut n = 1;
+        for c in [c1, c2].iter().flatten() {
+            if *c > bounds[n - 1] && *c < one {
+                bounds[n] = *c;
+                n += 1;
+            }
+        }
+
+        for window in bounds[..=n].windows(2) {
+            let (a, b) = (window[0], window[1]);
+            let (xa, xb) = (self.position(a), self.position(b));
+
+            if xb > xa && xb >= k + one {
+                let tau = self.solve(a, b, k + one, Direction::Forward);
+                return Some((Direction::Forward, tau));
+            }
+            if xb < xa && xb <= k - one {
+                let tau = self.solve(a, b, k - one, Direction::Backward);
+                return Some((Direction::Backward, tau));
+            }
+        }
+
+        None
+    }
+
+    /// Compute the distance covered from `tau` to the end of the segment
+    ///
+    /// Unlike the difference between the positions, this includes any
+    /// distance covered while overshooting the end point and coming back.
+    fn variation(&self, tau: Num) -> Num {
+        let (c1, c2) = self.critical_points();
+
+        let mut variation = Num::zero();
+        let mut a = tau;
+        for b in [c1, c2, Some(Num::one())].iter().flatten() {
+            if *b > a {
+                variation =
+                    variation + abs(self.position(*b) - self.position(a));
+                a = *b;
+            }
+        }
+
+        variation
+    }
+
+    /// Find the points in `(0, 1)` where the slope is zero, in ascending order
+    fn critical_points(&self) -> (Option<Num>, Option<Num>) {
+        let zero = Num::zero();
+        let two = Num::one() + Num::one();
+        let three = two + Num::one();
+
+        // Solve `a * τ² + b * τ + c = 0`.
+        let a = three * self.a3;
+        let b = two * self.a2;
+        let c = self.a1;
+
+        let in_range = |tau: Num| {
+            if tau > zero && tau < Num::one() {
+                Some(tau)
+            } else {
+                None
+            }
+        };
+
+        if a.is_zero() {
+            if b.is_zero() {
+                return (None, None);
+            }
+            return (in_range(zero - c / b), None);
+        }
+
+        let discriminant = b * b - two * two * a * c;
+        if discriminant < zero {
+            return (None, None);
+        }
+        let root = discriminant.sqrt();
+
+        let r1 = (zero - b - root) / (two * a);
+        let r2 = (zero - b + root) / (two * a);
+        let (r1, r2) = if r1 < r2 { (r1, r2) } else { (r2, r1) };
+
+        (in_range(r1), in_range(r2))
+    }
+
+    /// Find the time in `(a, b]` at which `target` is reached
+    ///
+    /// The position must be monotonic in the interval, and reach the target
+    /// at `b` at the latest. Returns a time at which the target has been
+    /// reached, as close to the exact one as the numeric type allows.
+    fn solve(&self, a: Num, b: Num, target: Num, direction: Direction) -> Num {
+        const MAX_ITERATIONS: usize = 64;
+
+        let two = Num::one() + Num::one();
+
+        // Make the error increase with time, no matter the direction.
+        let error = |tau: Num| match direction {
+            Direction::Forward => self.position(tau) - target,
+            Direction::Backward => target - self.position(tau),
+        };
+
+        if error(b).is_zero() {
+            return b;
+        }
+
+        let (mut lo, mut hi) = (a, b);
+        let mut tau = b;
+        for _ in 0..MAX_ITERATIONS {
+            let e = error(tau);
+            if e.is_zero() {
+                return tau;
+            }
+            if e > Num::zero() {
+                hi = tau;
+            } else {
+                lo = tau;
+            }
+
+            let slope = match direction {
+                Direction::Forward => self.slope(tau),
+                Direction::Backward => Num::zero() - self.slope(tau),
+            };
+            let newton = if slope > Num::zero() {
+                Some(tau - e / slope)
+            } else {
+                None
+            };
+            let next = match newton {
+                Some(next) if next > lo && next < hi => next,
+                _ => lo + (hi - lo) / two,
+            };
+
+            if next <= lo || next >= hi {
+                break;
+            }
+            tau = next;
+        }
+
+        hi
+    }
+}
+
+fn abs<Num>(value: Num) -> Num
+where
+    Num: PartialOrd + num_traits::Zero + ops::Sub<Output = Num>,
+{
+    if value < Num::zero() {
+        Num::zero() - value
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{Direction, MotionProfile as _};
+
+    use super::{Point, Pvt};
+
+    fn point(position: i32, velocity: f64, time: f64) -> Point<f64> {
+        Point {
+            position,
+            velocity,
+            time,
+        }
+    }
+
+    #[test]
+    fn pvt_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<Pvt<f32, 8>>();
+    }
+
+    #[test]
+    fn pvt_should_pass_through_points() {
+        let mut pvt = Pvt::<f64, 8>::new();
+        pvt.push(point(0, 0.0, 0.0)).unwrap();
+        pvt.push(point(100, 200.0, 1.0)).unwrap();
+        pvt.push(point(300, 200.0, 2.0)).unwrap();
+        pvt.push(point(400, 0.0, 3.0)).unwrap();
+
+        let mut time = 0.0;
+        let mut times = Vec::new();
+        while let Some((direction, delay)) = pvt.next_step() {
+            assert_eq!(direction, Direction::Forward);
+            time += delay;
+            times.push(time);
+        }
+
+        assert_eq!(times.len(), 400);
+        assert_eq!(pvt.position(), 400);
+        assert_abs_diff_eq!(times[99], 1.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(times[299], 2.0, epsilon = 1e-9);
+        assert_abs_diff_eq!(times[399], 3.0, epsilon = 1e-9);
+
+        // Constant velocity between the middle points.
+        for pair in times[100..300].windows(2) {
+            assert_abs_diff_eq!(pair[1] - pair[0], 1.0 / 200.0, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn pvt_should_interpolate_cubic_hermite_polynomial() {
+        let mut pvt = Pvt::<f64, 8>::new();
+        pvt.push(point(0, 50.0, 0.0)).unwrap();
+        pvt.push(point(200, 300.0, 1.0)).unwrap();
+
+        // Hermite polynomial for p0 = 0, v0 = 50, p1 = 200, v1 = 300, T = 1.
+        let position = |t: f64| {
+            let t2 = t * t;
+            let t3 = t2 * t;
+            (t3 - 2.0 * t2 + t) * 50.0
+                + (-2.0 * t3 + 3.0 * t2) * 200.0
+                + (t3 - t2) * 300.0
+        };
+
+        let mut time = 0.0;
+        let mut step = 0;
+        while let Some((_, delay)) = pvt.next_step() {
+            time += delay;
+            step += 1;
+            assert_abs_diff_eq!(position(time), step as f64, epsilon = 1e-6);
+        }
+        assert_eq!(step, 200);
+    }
+
+    #[test]
+    fn pvt_should_change_direction() {
+        let mut pvt = Pvt::<f64, 8>::new();
+        pvt.push(point(0, 0.0, 0.0)).unwrap();
+        pvt.push(point(100, 0.0, 1.0)).unwrap();
+        pvt.push(point(-50, 0.0, 2.0)).unwrap();
+
+        let mut position = 0;
+        let mut reversals = 0;
+        let mut direction_prev = None;
+        while let Some((direction, _)) = pvt.next_step() {
+            position += direction.sign();
+            if direction_prev.is_some_and(|prev| prev != direction) {
+                reversals += 1;
+            }
+            direction_prev = Some(direction);
+        }
+
+        assert_eq!(reversals, 1);
+        assert_eq!(position, -50);
+        assert_eq!(pvt.position(), -50);
+    }
+
+    #[test]
+    fn pvt_should_overshoot_without_chattering() {
+        // The velocity at the end point makes the trajectory overshoot the
+        // point and turn around.
+        let mut pvt = Pvt::<f64, 8>::new();
+        pvt.push(point(0, 0.0, 0.0)).unwrap();
+        pvt.push(point(10, -20.0, 1.0)).unwrap();
+
+        let directions: Vec<Direction> =
+            core::iter::from_fn(|| pvt.next_step().map(|(d, _)| d)).collect();
+
+        let reversals = directions.windows(2).filter(|w| w[0] != w[1]).count();
+        assert_eq!(reversals, 1);
+        assert_eq!(pvt.position(), 10);
+    }
+
+    #[test]
+    fn pvt_should_count_overshoot_in_steps_to_stop() {
+        let mut pvt = Pvt::<f64, 8>::new();
+        pvt.push(point(0, 0.0, 0.0)).unwrap();
+        pvt.push(point(10, -20.0, 1.0)).unwrap();
+        pvt.next_step();
+
+        let steps_to_stop = pvt.steps_to_stop();
+        let steps = core::iter::from_fn(|| pvt.next_step()).count() as u32;
+
+        assert!(steps > pvt.steps_remaining() + 9);
+        assert!(steps <= steps_to_stop);
+        assert!(steps + 1 >= steps_to_stop);
+    }
+
+    #[test]
+    fn pvt_should_retarget_from_current_position() {
+        let mut pvt = Pvt::<f64, 8>::new();
+        pvt.push(point(0, 0.0, 0.0)).unwrap();
+        pvt.push(point(100, 100.0, 1.0)).unwrap();
+        pvt.push(point(200, 0.0, 2.0)).unwrap();
+        for _ in 0..150 {
+            pvt.next_step();
+        }
+
+        let velocity = pvt.current_velocity();
+        pvt.enter_position_mode(200.0, 100);
+        assert_eq!(pvt.steps_remaining(), 100);
+
+        let delays: Vec<f64> = pvt.delays().collect();
+        assert_eq!(delays.len(), 100);
+        assert_eq!(pvt.position(), 250);
+
+        // The velocity is continuous, and never exceeds the maximum.
+        assert_abs_diff_eq!(1.0 / delays[0], velocity, epsilon = 2.0);
+        assert!(delays.iter().all(|delay| 1.0 / delay <= 200.0));
+    }
+
+    #[test]
+    fn pvt_should_clamp_target_to_position_range() {
+        let mut pvt = Pvt::<f64, 8>::new();
+
+        pvt.enter_position_mode(1000.0, u32::MAX);
+        assert_eq!(pvt.steps_remaining(), i32::MAX as u32);
+
+        for _ in 0..10 {
+            let (direction, _) = pvt.next_step().unwrap();
+            assert_eq!(direction, Direction::Forward);
+        }
+        assert_eq!(pvt.position(), 10);
+        assert_eq!(pvt.steps_remaining(), i32::MAX as u32 - 10);
+    }
+
+    #[test]
+    fn pvt_should_reject_points_when_full_or_out_of_order() {
+        let mut pvt = Pvt::<f64, 2>::new();
+
+        pvt.push(point(0, 0.0, 1.0)).unwrap();
+        assert_eq!(pvt.push(point(10, 0.0, 1.0)), Err(point(10, 0.0, 1.0)));
+        pvt.push(point(10, 0.0, 2.0)).unwrap();
+        assert_eq!(pvt.free_capacity(), 0);
+        assert_eq!(pvt.push(point(20, 0.0, 3.0)), Err(point(20, 0.0, 3.0)));
+
+        // Reaching a point frees its slot.
+        pvt.next_step();
+        assert_eq!(pvt.free_capacity(), 1);
+        pvt.push(point(20, 0.0, 3.0)).unwrap();
+
+        let steps = core::iter::from_fn(|| pvt.next_step()).count();
+        assert_eq!(steps, 19);
+        assert_eq!(pvt.position(), 20);
+    }
+
+    #[test]
+    fn pvt_should_continue_after_running_empty() {
+        let mut pvt = Pvt::<f64, 4>::new();
+        pvt.push(point(0, 0.0, 0.0)).unwrap();
+        pvt.push(point(10, 0.0, 1.0)).unwrap();
+        assert_eq!(core::iter::from_fn(|| pvt.next_step()).count(), 10);
+        assert!(pvt.is_idle());
+
+        pvt.push(point(20, 0.0, 2.0)).unwrap();
+        assert!(!pvt.is_idle());
+        assert_eq!(core::iter::from_fn(|| pvt.next_step()).count(), 10);
+        assert_eq!(pvt.position(), 20);
+    }
+
+    #[test]
+    fn pvt_should_work_with_fixed_point_numbers() {
+        use fixed::types::I32F32;
+
+        let mut float = Pvt::<f64, 8>::new();
+        let mut fixed = Pvt::<I32F32, 8>::new();
+
+        for &(position, velocity, time) in
+            &[(0, 0.0, 0.0), (100, 200.0, 1.0), (50, -100.0, 2.0)]
+        {
+            float.push(point(position, velocity, time)).unwrap();
+            fixed
+                .push(Point {
+                    position,
+                    velocity: I32F32::from_num(velocity),
+                    time: I32F32::from_num(time),
+                })
+                .unwrap();
+        }
+
+        let mut count = 0;
+        while let Some((direction, delay)) = float.next_step() {
+            let (fixed_direction, fixed_delay) = fixed.next_step().unwrap();
+            assert_eq!(fixed_direction, direction);
+            assert_abs_diff_eq!(
+                fixed_delay.to_num::<f64>(),
+                delay,
+                epsilon = 1e-6
+            );
+            count += 1;
+        }
+        assert!(count > 150);
+        assert_eq!(fixed.next_step(), None);
+    }
+}
@@ -0,0 +1,571 @@
+//! Queue of moves that are executed back to back
+//!
+//! See [`Queue`] and [`Executor`].
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::MotionProfile;
+
+/// A fixed-capacity queue of moves
+///
+/// Holds up to `N` moves, which are executed by an [`Executor`], once the
+/// current motion has finished. This struct doesn't allocate and works
+/// without the standard library.
+///
+/// # Lock-Free Use
+///
+/// If the queue is only accessed from a single execution context, use it
+/// directly, through [`Queue::push`] and [`Executor::source_mut`].
+///
+/// If moves are added from the main loop, while the steps are generated in an
+/// interrupt handler, use [`Queue::split`] instead. The resulting [`Producer`]
+/// and [`Consumer`] can be moved to different execution contexts, and access
+/// the queue without locking. They only require atomic loads and stores,
+/// which are available on all targets.
+pub struct Queue<Velocity, const N: usize = 8> {
+    moves: [UnsafeCell<Option<Move<Velocity>>>; N],
+
+    // Both indices count up to `2 * N`, which allows distinguishing a full
+    // queue from an empty one, without wasting a slot.
+    read: AtomicUsize,
+    write: AtomicUsize,
+}
+
+impl<Velocity, const N: usize> Queue<Velocity, N> {
+    /// Create a new, empty instance of `Queue`
+    ///
+    /// This is a `const fn`, so the queue can be created at compile time.
+    /// [`Queue::split`] needs mutable access though, which a plain `static`
+    /// doesn't provide. To share a queue between execution contexts, put it
+    /// into a type that hands out a `&'static mut` reference once, like
+    /// `StaticCell` from the [static_cell] crate.
+    ///
+    /// [static_cell]: https://crates.io/crates/static_cell
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `N` is zero.
+    pub const fn new() -> Self {
+        assert!(N > 0, "Queue must have a capacity of at least one move");
+
+        Self {
+            moves: [const { UnsafeCell::new(None) }; N],
+            read: AtomicUsize::new(0),
+            write: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add a move to the end of the queue
+    ///
+    /// Returns the move as an error, if the queue is full.
+    pub fn push(
+        &mut self,
+        move_: Move<Velocity>,
+    ) -> Result<(), Move<Velocity>> {
+        self.enqueue(move_)
+    }
+
+    /// Remove the move at the front of the queue
+    pub fn pop(&mut self) -> Option<Move<Velocity>> {
+        self.dequeue()
+    }
+
+    /// Return the number of moves in the queue
+    pub fn len(&self) -> usize {
+        len::<N>(
+            self.read.load(Ordering::Acquire),
+            self.write.load(Ordering::Acquire),
+        )
+    }
+
+    /// Indicate whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Return the number of moves the queue can hold
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Split the queue into a producer and a consumer
+    ///
+    /// See the documentation of [`Queue`] for details.
+    pub fn split(
+        &mut self,
+    ) -> (Producer<'_, Velocity, N>, Consumer<'_, Velocity, N>) {
+        (
+            Producer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+            Consumer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    /// Must only be called by one producer at a time
+    fn enqueue(&self, move_: Move<Velocity>) -> Result<(), Move<Velocity>> {
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        if len::<N>(read, write) == N {
+            return Err(move_);
+        }
+
+        // SAFETY: There's only one producer at a time, and the consumer
+        // doesn't access this slot, until the write index has been advanced
+        // past it below.
+        unsafe { *self.moves[write % N].get() = Some(move_) };
+        self.write.store(advance::<N>(write), Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Must only be called by one consumer at a time
+    fn dequeue(&self) -> Option<Move<Velocity>> {
+        let read = self.read.load(Ordering::Relaxed);
+        let write = self.write.load(Ordering::Acquire);
+        if read == write {
+            return None;
+        }
+
+        // SAFETY: There's only one consumer at a time, and the producer
+        // doesn't access this slot, until the read index has been advanced
+        // past it below.
+        let move_ = unsafe { (*self.moves[read % N].get()).take() };
+        self.read.store(advance::<N>(read), Ordering::Release);
+
+        move_
+    }
+}
+
+impl<Velocity, const N: usize> Default for Queue<Velocity, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Velocity, const N: usize> fmt::Debug for Queue<Velocity, N> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .field("capacity", &N)
+            .finish()
+    }
+}
+
+/// Adds moves to a [`Queue`]
+///
+/// Created by [`Queue::split`].
+#[derive(Debug)]
+pub struct Producer<'r, Velocity, const N: usize> {
+    queue: &'r Queue<Velocity, N>,
+
+    // Only one producer may access the queue at a time. Since this struct can
+    // only be moved, not shared, that is guaranteed.
+    _not_sync: PhantomData<*const ()>,
+}
+
+impl<Velocity, const N: usize> Producer<'_, Velocity, N> {
+    /// Add a move to the end of the queue
+    ///
+    /// Returns the move as an error, if the queue is full.
+    pub fn push(
+        &mut self,
+        move_: Move<Velocity>,
+    ) -> Result<(), Move<Velocity>> {
+        self.queue.enqueue(move_)
+    }
+
+    /// Return the number of moves that can be added before the queue is full
+    pub fn free_capacity(&self) -> usize {
+        N - self.queue.len()
+    }
+}
+
+// SAFETY: The producer only accesses the queue as described in the
+// documentation of `Queue::enqueue`, which is safe to do concurrently with the
+// consumer.
+unsafe impl<Velocity, const N: usize> Send for Producer<'_, Velocity, N> where
+    Velocity: Send
+{
+}
+
+/// Removes moves from a [`Queue`]
+///
+/// Created by [`Queue::split`]. Pass it to an [`Executor`], to execute the
+/// moves.
+#[derive(Debug)]
+pub struct Consumer<'r, Velocity, const N: usize> {
+    queue: &'r Queue<Velocity, N>,
+
+    // See `Producer`.
+    _not_sync: PhantomData<*const ()>,
+}
+
+impl<Velocity, const N: usize> Consumer<'_, Velocity, N> {
+    /// Remove the move at the front of the queue
+    pub fn pop(&mut self) -> Option<Move<Velocity>> {
+        self.queue.dequeue()
+    }
+}
+
+// SAFETY: The consumer only accesses the queue as described in the
+// documentation of `Queue::dequeue`, which is safe to do concurrently with the
+// producer.
+unsafe impl<Velocity, const N: usize> Send for Consumer<'_, Velocity, N> where
+    Velocity: Send
+{
+}
+
+/// A move that can be added to a [`Queue`]
+///
+/// Equivalent to the arguments of [`MotionProfile::enter_position_mode`].
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Move<Velocity> {
+    /// The maximum velocity of the move
+    pub max_velocity: Velocity,
+
+    /// The number of steps of the move
+    pub num_steps: u32,
+}
+
+/// A source of moves for an [`Executor`]
+///
+/// Implemented by [`Queue`] and [`Consumer`].
+pub trait MoveSource<Velocity> {
+    /// Remove the next move from the source
+    fn next_move(&mut self) -> Option<Move<Velocity>>;
+
+    /// Indicate whether the source has no more moves
+    fn is_empty(&self) -> bool;
+}
+
+impl<Velocity, const N: usize> MoveSource<Velocity> for Queue<Velocity, N> {
+    fn next_move(&mut self) -> Option<Move<Velocity>> {
+        self.pop()
+    }
+
+    fn is_empty(&self) -> bool {
+        Queue::is_empty(self)
+    }
+}
+
+impl<Velocity, const N: usize> MoveSource<Velocity>
+    for Consumer<'_, Velocity, N>
+{
+    fn next_move(&mut self) -> Option<Move<Velocity>> {
+        self.pop()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+/// Executes moves from a [`MoveSource`], back to back
+///
+/// Wraps a [`MotionProfile`] and implements [`MotionProfile`] itself. Once the
+/// current motion has finished, the next move is started from within the same
+/// call to [`MotionProfile::next_delay`] that would otherwise have ended the
+/// motion. This means no step deadline is missed between moves, as long as the
+/// next move has been queued in time.
+///
+/// [`MotionProfile::enter_position_mode`] replaces the current motion, like it
+/// does for the wrapped motion profile. Queued moves are started after that
+/// motion has finished.
+///
+/// # Paused Motions
+///
+/// The next move is only started, once the motion profile is idle. A paused
+/// motion (see [`Pausable::pause`]) is not idle, which means it doesn't cause
+/// the next move to start early.
+///
+/// [`Pausable::pause`]: crate::pause::Pausable::pause
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Executor<Profile, Source> {
+    profile: Profile,
+    source: Source,
+}
+
+impl<Profile, Source> Executor<Profile, Source>
+where
+    Profile: MotionProfile,
+    Source: MoveSource<Profile::Velocity>,
+{
+    /// Create a new instance of `Executor`
+    pub fn new(profile: Profile, source: Source) -> Self {
+        Self { profile, source }
+    }
+
+    /// Access the motion profile
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+
+    /// Access the motion profile mutably
+    ///
+    /// Use this to pause or resume the current motion, for example.
+    pub fn profile_mut(&mut self) -> &mut Profile {
+        &mut self.profile
+    }
+
+    /// Access the source of moves mutably
+    ///
+    /// Use this to add moves to a [`Queue`] that is owned by the executor.
+    pub fn source_mut(&mut self) -> &mut Source {
+        &mut self.source
+    }
+
+    /// Release the motion profile and the source of moves
+    pub fn into_inner(self) -> (Profile, Source) {
+        (self.profile, self.source)
+    }
+}
+
+impl<Profile, Source> MotionProfile for Executor<Profile, Source>
+where
+    Profile: MotionProfile,
+    Source: MoveSource<Profile::Velocity>,
+{
+    type Velocity = Profile::Velocity;
+    type Delay = Profile::Delay;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        self.profile.enter_position_mode(max_velocity, num_steps);
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        loop {
+            if let Some(delay) = self.profile.next_delay() {
+                return Some(delay);
+            }
+            if !self.profile.is_idle() {
+                return None;
+            }
+
+            let move_ = self.source.next_move()?;
+            self.profile
+                .enter_position_mode(move_.max_velocity, move_.num_steps);
+        }
+    }
+
+    /// Return the number of steps left in the current motion
+    ///
+    /// Doesn't include the steps of queued moves.
+    fn steps_remaining(&self) -> u32 {
+        self.profile.steps_remaining()
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        self.profile.current_velocity()
+    }
+
+    fn is_idle(&self) -> bool {
+        self.profile.is_idle() && self.source.is_empty()
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        self.profile.steps_to_stop()
+    }
+}
+
+fn advance<const N: usize>(index: usize) -> usize {
+    if index + 1 == 2 * N {
+        0
+    } else {
+        index + 1
+    }
+}
+
+fn len<const N: usize>(read: usize, write: usize) -> usize {
+    if write >= read {
+        write - read
+    } else {
+        write + 2 * N - read
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{pause::Pausable, MotionProfile, Trapezoidal};
+
+    use super::{Executor, Move, Queue};
+
+    /// Provides the `Default` implementation needed for the `MotionProfile`
+    /// test suite in `crate::util::testing`
+    struct TestExecutor(Executor<Trapezoidal<f32>, Queue<f32>>);
+
+    impl Default for TestExecutor {
+        fn default() -> Self {
+            Self(Executor::new(Trapezoidal::default(), Queue::new()))
+        }
+    }
+
+    impl MotionProfile for TestExecutor {
+        type Velocity = f32;
+        type Delay = f32;
+
+        fn enter_position_mode(&mut self, max_velocity: f32, num_steps: u32) {
+            self.0.enter_position_mode(max_velocity, num_steps)
+        }
+
+        fn next_delay(&mut self) -> Option<f32> {
+            self.0.next_delay()
+        }
+
+        fn steps_remaining(&self) -> u32 {
+            self.0.steps_remaining()
+        }
+
+        fn current_velocity(&self) -> f32 {
+            self.0.current_velocity()
+        }
+
+        fn is_idle(&self) -> bool {
+            self.0.is_idle()
+        }
+
+        fn steps_to_stop(&self) -> u32 {
+            self.0.steps_to_stop()
+        }
+    }
+
+    fn move_(num_steps: u32) -> Move<f32> {
+        Move {
+            max_velocity: 1000.0,
+            num_steps,
+        }
+    }
+
+    #[test]
+    fn executor_should_pass_motion_profile_test_suite() {
+        crate::util::testing::test::<TestExecutor>();
+    }
+
+    #[test]
+    fn queue_should_return_moves_in_order() {
+        let mut queue: Queue<f32, 3> = Queue::new();
+
+        // Go around the ring buffer a few times.
+        for i in 0..10 {
+            queue.push(move_(i)).unwrap();
+            queue.push(move_(i + 100)).unwrap();
+            queue.push(move_(i + 200)).unwrap();
+            assert_eq!(queue.push(move_(0)), Err(move_(0)));
+            assert_eq!(queue.len(), 3);
+
+            assert_eq!(queue.pop(), Some(move_(i)));
+            assert_eq!(queue.pop(), Some(move_(i + 100)));
+            queue.push(move_(i + 300)).unwrap();
+            assert_eq!(queue.pop(), Some(move_(i + 200)));
+            assert_eq!(queue.pop(), Some(move_(i + 300)));
+            assert_eq!(queue.pop(), None);
+            assert!(queue.is_empty());
+        }
+    }
+
+    #[test]
+    fn executor_should_start_next_move_without_gap() {
+        let mut executor =
+            Executor::new(Trapezoidal::new(6000.0), Queue::<f32>::new());
+        executor.enter_position_mode(1000.0, 100);
+        executor.source_mut().push(move_(0)).unwrap();
+        executor.source_mut().push(move_(200)).unwrap();
+        executor.source_mut().push(move_(300)).unwrap();
+
+        let mut steps = 0;
+        while executor.next_delay().is_some() {
+            steps += 1;
+            assert_eq!(executor.is_idle(), steps == 600);
+        }
+
+        assert_eq!(steps, 600);
+        assert!(executor.is_idle());
+    }
+
+    #[test]
+    fn executor_should_not_start_next_move_while_paused() {
+        let mut executor = Executor::new(
+            Pausable::new(Trapezoidal::new(6000.0)),
+            Queue::<f32>::new(),
+        );
+        executor.source_mut().push(move_(1000)).unwrap();
+        executor.source_mut().push(move_(1000)).unwrap();
+
+        for _ in 0..100 {
+            executor.next_delay().unwrap();
+        }
+        executor.profile_mut().pause();
+
+        let mut steps = 100;
+        while executor.next_delay().is_some() {
+            steps += 1;
+        }
+        assert!(steps < 1000);
+        assert_eq!(executor.source_mut().len(), 1);
+        assert!(!executor.is_idle());
+
+        executor.profile_mut().resume();
+        while executor.next_delay().is_some() {
+            steps += 1;
+        }
+        assert_eq!(steps, 2000);
+    }
+
+    #[test]
+    fn split_queue_should_work_across_threads() {
+        let mut queue: Queue<f32, 4> = Queue::new();
+        let (mut producer, consumer) = queue.split();
+
+        let steps = std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for i in 1..=100 {
+                    let mut next = move_(i);
+                    while let Err(move_) = producer.push(next) {
+                        next = move_;
+                        std::thread::yield_now();
+                    }
+                }
+            });
+
+            let executor = scope.spawn(move || {
+                let mut executor =
+                    Executor::new(Trapezoidal::new(6000.0), consumer);
+
+                let mut steps = 0;
+                while steps < 5050 {
+                    if executor.next_delay().is_some() {
+                        steps += 1;
+                    } else {
+                        std::thread::yield_now();
+                    }
+                }
+                assert!(executor.is_idle());
+                steps
+            });
+
+            executor.join().unwrap()
+        });
+
+        assert_eq!(steps, 5050);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+//! Quintic motion profile
+//!
+//! See [`Quintic`].
+
+use core::ops;
+
+use az::Az as _;
+
+use crate::{
+    util::{
+        ramp::{Motion, Shape},
+        sub,
+        traits::{Ceil, Sqrt},
+    },
+    MotionProfile, TimedMoveError,
+};
+
+/// Quintic (minimum-jerk) motion profile
+///
+/// Generates a point-to-point motion whose position follows the quintic
+/// polynomial `x(t) = D * (10s³ - 15s⁴ + 6s⁵)`, with `s = t / T`, for a motion
+/// of `D` steps that takes the time `T`. This is the motion that minimizes the
+/// jerk. Velocity and acceleration are zero at both ends, and the acceleration
+/// is continuous throughout the motion, unlike with [`Trapezoidal`].
+///
+/// There is no plateau of constant velocity. The velocity peaks at the middle
+/// of the motion, at `15D / 8T`. The acceleration peaks at `10D / (√3 * T²)`.
+/// This makes the profile best suited for short, precise moves.
+///
+/// The time of each step is computed by solving the polynomial for the
+/// position of the step, using Newton's method. This requires a few
+/// multiplications and divisions per step, but no transcendental functions.
+///
+/// Create an instance of this struct using [`Quintic::new`], then use the API
+/// defined by [`MotionProfile`] (which this struct implements) to generate the
+/// ramp. To move in a given time, use [`Quintic::enter_timed_position_mode`].
+///
+/// # Acceleration Ramp
+///
+/// This struct will generate a ramp with the following attributes:
+/// - The velocity will always be equal to or less than the maximum velocity
+///   passed to [`MotionProfile::enter_position_mode`].
+/// - The duration of the motion is chosen as short as possible, without
+///   exceeding the maximum velocity or the maximum acceleration passed to the
+///   constructor.
+/// - Deceleration mirrors acceleration.
+///
+/// Calling [`MotionProfile::enter_position_mode`] while a motion is in
+/// progress re-targets that motion, relative to the current position:
+/// - If the new target is further away, the motion continues at its peak
+///   velocity, before ramping down, which adds a plateau of constant velocity
+///   in the middle of the motion.
+/// - If the new target is closer, the ramp up is cut short, which makes the
+///   acceleration jump.
+/// - If the new target is too close to stop on it, the motion comes to a stop
+///   as soon as possible instead, overshooting the target.
+///
+/// The velocity of a motion in progress doesn't change, so the new maximum
+/// velocity only applies to motions that start from a stand-still.
+///
+/// # Unit of Time
+///
+/// This code is agnostic on which unit of time is used. If you provide the
+/// maximum acceleration and maximum velocity in steps per second, the unit of
+/// the delay returned will be seconds.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent the
+/// maximum acceleration, maximum velocity, and delays per step. It is set to a
+/// 64-bit fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other type from the
+/// `fixed` crate. Please note that you need to enable support for `f32`/`f64`
+/// explicitly. Check out the section on Cargo features from the documentation
+/// in the root module for more information.
+///
+/// [`Trapezoidal`]: crate::Trapezoidal
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quintic<Num = DefaultNum> {
+    max_accel: Num,
+    motion: Option<Motion<Num>>,
+}
+
+impl<Num> Quintic<Num> {
+    /// Create a new instance of `Quintic`
+    ///
+    /// Accepts the maximum acceleration in steps per (unit of time)^2 as an
+    /// argument. It must not be zero. See the struct documentation for
+    /// information about units of time.
+    pub fn new(max_accel: Num) -> Self {
+        Self {
+            max_accel,
+            motion: None,
+        }
+    }
+}
+
+impl<Num> Quintic<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + Sqrt
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    /// Move the given number of steps in the given time
+    ///
+    /// Like [`MotionProfile::enter_position_mode`], but instead of the maximum
+    /// velocity, the duration of the motion is passed.
+    ///
+    /// Returns [`TimedMoveError::NotIdle`], if a motion is in progress, as the
+    /// duration can only be met by a motion that starts from a stand-still.
+    /// Returns [`TimedMoveError::DurationTooShort`], if the motion can't be
+    /// made in the given time without exceeding the maximum acceleration.
+    pub fn enter_timed_position_mode(
+        &mut self,
+        num_steps: u32,
+        duration: Num,
+    ) -> Result<(), TimedMoveError> {
+        if !self.is_idle() {
+            return Err(TimedMoveError::NotIdle);
+        }
+        if num_steps == 0 {
+            self.motion = None;
+            return Ok(());
+        }
+
+        if duration < self.min_duration(num_steps) || duration.is_zero() {
+            return Err(TimedMoveError::DurationTooShort);
+        }
+
+        self.motion = Some(plan(duration, num_steps));
+        Ok(())
+    }
+
+    /// Re-target the motion in progress, if there is one
+    ///
+    /// Returns `false`, if there's no motion in progress.
+    fn retarget(&mut self, stop: bool, num_steps: u32) -> bool {
+        match &mut self.motion {
+            Some(motion) if motion.is_moving() => {
+                let num_steps = if stop { 0 } else { num_steps };
+                motion.retarget::<Polynomial>(num_steps);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Compute the duration at which the acceleration peaks at the maximum
+    fn min_duration(&self, num_steps: u32) -> Num {
+        // Solved `a = 10D / (√3 * T²)` for `T`.
+        let ten = 10.az::<Num>();
+        let distance = num_steps.az::<Num>();
+        (ten * distance / (3.az::<Num>().sqrt() * self.max_accel)).sqrt()
+    }
+}
+
+// Needed for the `MotionProfile` test suite in `crate::util::testing`.
+#[cfg(test)]
+impl Default for Quintic<f32> {
+    fn default() -> Self {
+        Self::new(6000.0)
+    }
+}
+
+impl<Num> MotionProfile for Quintic<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + Sqrt
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        if self.retarget(max_velocity.is_zero(), num_steps) {
+            return;
+        }
+        if max_velocity.is_zero() || num_steps == 0 {
+            self.motion = None;
+            return;
+        }
+
+        // Solved `v = 15D / 8T` for `T`.
+        let distance = num_steps.az::<Num>();
+        let duration =
+            15.az::<Num>() * distance / (8.az::<Num>() * max_velocity);
+
+        let min_duration = self.min_duration(num_steps);
+        let duration = if duration < min_duration {
+            min_duration
+        } else {
+            duration
+        };
+
+        self.motion = Some(plan(duration, num_steps));
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        self.motion.as_mut()?.next_delay::<Polynomial>()
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.motion.as_ref().map_or(0, Motion::steps_remaining)
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        self.motion
+            .as_ref()
+            .map_or(Num::zero(), Motion::current_velocity)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.motion.as_ref().is_none_or(Motion::is_idle)
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        self.motion.as_ref().map_or(0, Motion::steps_to_stop)
+    }
+}
+
+/// The default numeric type used by [`Quintic`]
+pub type DefaultNum = fixed::FixedU64<typenum::U32>;
+
+/// Plan a motion from a stand-still
+///
+/// The motion is parametrized by the normalized time `s = t / T`, which goes
+/// from 0 to 1 over the duration `T` of the motion. The first half of the
+/// motion is the ramp up, the second half mirrors it, so only the convex half
+/// of the polynomial is ever used.
+fn plan<Num>(duration: Num, num_steps: u32) -> Motion<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    // The peak velocity is `15D / 8T`.
+    let distance = num_steps.az::<Num>();
+    let velocity = 15.az::<Num>() * distance / (8.az::<Num>() * duration);
+
+    Motion::plan::<Polynomial>(distance, duration, half(), velocity, num_steps)
+}
+
+/// The shape of the ramp, `10s³ - 15s⁴ + 6s⁵`
+struct Polynomial;
+
+impl<Num> Shape<Num> for Polynomial
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    fn distance(s: Num) -> Num {
+        polynomial(s).0
+    }
+
+    fn slope(s: Num) -> Num {
+        polynomial(s).1
+    }
+}
+
+/// Evaluate `10s³ - 15s⁴ + 6s⁵` and its derivative `30s²(1 - s)²`
+///
+/// Only valid on `[0, 1]`. The terms are arranged so that no intermediate
+/// result is negative, which unsigned types couldn't represent.
+fn polynomial<Num>(s: Num) -> (Num, Num)
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    let s2 = s * s;
+    let s3 = s2 * s;
+    let one_minus_s = sub(Num::one(), s);
+
+    let factor = sub(10.az::<Num>() + 6.az::<Num>() * s2, 15.az::<Num>() * s);
+    let value = s3 * factor;
+    let slope = 30.az::<Num>() * s2 * one_minus_s * one_minus_s;
+
+    (value, slope)
+}
+
+fn half<Num>() -> Num
+where
+    Num: num_traits::One + ops::Add<Output = Num> + ops::Div<Output = Num>,
+{
+    Num::one() / (Num::one() + Num::one())
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{MotionProfile as _, Quintic, TimedMoveError};
+
+    #[test]
+    fn quintic_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<Quintic<f32>>();
+    }
+
+    #[test]
+    fn quintic_should_follow_minimum_jerk_trajectory() {
+        let num_steps = 1000;
+        let duration = 2.0;
+
+        let mut quintic = Quintic::<f64>::new(100_000.0);
+        quintic
+            .enter_timed_position_mode(num_steps, duration)
+            .unwrap();
+
+        let mut time = 0.0;
+        for (step, delay) in quintic.delays().enumerate() {
+            time += delay;
+
+            let s: f64 = time / duration;
+            let expected = num_steps as f64
+                * (10.0 * s.powi(3) - 15.0 * s.powi(4) + 6.0 * s.powi(5));
+            assert_abs_diff_eq!(expected, (step + 1) as f64, epsilon = 1e-6);
+        }
+
+        assert_abs_diff_eq!(time, duration, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn quintic_should_reach_peak_velocity_in_the_middle() {
+        let max_velocity = 1000.0;
+
+        let mut quintic = Quintic::<f64>::new(100_000.0);
+        quintic.enter_position_mode(max_velocity, 2000);
+
+        let velocities: Vec<f64> = quintic.velocities().collect();
+        let peak = velocities.iter().copied().fold(0.0, f64::max);
+        let middle = velocities[999];
+
+        assert_abs_diff_eq!(peak, max_velocity, epsilon = 1.0);
+        assert_abs_diff_eq!(middle, max_velocity, epsilon = 1.0);
+    }
+
+    #[test]
+    fn quintic_should_respect_max_acceleration() {
+        let max_accel = 6000.0;
+
+        for &num_steps in &[1, 2, 10, 100, 1000] {
+            let mut quintic = Quintic::<f64>::new(max_accel);
+            quintic.enter_position_mode(1000.0, num_steps);
+
+            for accel in quintic.accelerations::<f64>() {
+                assert!(accel.abs() <= max_accel * 1.01);
+            }
+        }
+    }
+
+    #[test]
+    fn quintic_should_be_symmetric() {
+        crate::util::testing::ramp_must_be_symmetric(Quintic::<f64>::new(
+            6000.0,
+        ));
+    }
+
+    #[test]
+    fn quintic_should_reject_too_short_duration() {
+        let mut quintic = Quintic::<f64>::new(6000.0);
+
+        // The peak acceleration would be 10 * 100 / (√3 * 0.01) ≈ 57735.
+        let result = quintic.enter_timed_position_mode(100, 0.1);
+        assert_eq!(result, Err(TimedMoveError::DurationTooShort));
+        assert_eq!(quintic.next_delay(), None);
+
+        let result = quintic.enter_timed_position_mode(100, 0.4);
+        assert_eq!(result, Ok(()));
+        assert_eq!(quintic.delays().count(), 100);
+    }
+
+    #[test]
+    fn quintic_should_reject_timed_motion_while_moving() {
+        let mut quintic = Quintic::<f64>::new(6000.0);
+
+        quintic.enter_position_mode(1000.0, 1000);
+        quintic.delays().take(10).for_each(drop);
+
+        let result = quintic.enter_timed_position_mode(1000, 2.0);
+        assert_eq!(result, Err(TimedMoveError::NotIdle));
+        assert_eq!(quintic.delays().count(), 990);
+    }
+
+    #[test]
+    fn quintic_should_work_with_fixed_point_numbers() {
+        use fixed::types::U32F32;
+
+        crate::util::testing::fixed_point_must_match_floating_point(
+            Quintic::<f64>::new(6000.0),
+            Quintic::<U32F32>::new(U32F32::from_num(6000)),
+        );
+    }
+}
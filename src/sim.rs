@@ -0,0 +1,587 @@
+//! Host-side simulation of motion profiles
+//!
+//! This module can run any [`MotionProfile`] to completion and record the
+//! result as a table, which can be exported as CSV or JSON. This is useful for
+//! tuning motion parameters on a desktop computer, before flashing them to the
+//! target.
+//!
+//! See [`Simulation`]. If you want to know, whether a given numeric type is
+//! accurate enough for your motion parameters, take a look at [`Accuracy`].
+//!
+//! This module is only available, if the **`std`** feature is enabled.
+
+use std::{fmt, io};
+
+use crate::MotionProfile;
+
+/// The result of running a motion profile to completion
+///
+/// Create an instance of this struct using [`Simulation::run`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Simulation {
+    /// The simulated steps, one per delay produced by the motion profile
+    pub rows: Vec<Row>,
+}
+
+impl Simulation {
+    /// Run the provided motion profile to completion
+    ///
+    /// Calls [`MotionProfile::next_delay`] until it returns `None`, and
+    /// records one [`Row`] per delay. Please note that this will never return,
+    /// if the motion profile produces an endless motion.
+    ///
+    /// The unit of time used in the simulation is the unit of the delay
+    /// returned by the motion profile.
+    pub fn run<Profile>(profile: &mut Profile) -> Self
+    where
+        Profile: MotionProfile,
+        Profile::Delay: az::Cast<f64>,
+    {
+        let mut rows: Vec<Row> = Vec::new();
+        let mut time = 0.0;
+
+        for (step, delay) in profile.delays().enumerate() {
+            let delay = az::cast::<_, f64>(delay);
+            let velocity = 1.0 / delay;
+
+            // The acceleration is computed the same way as
+            // `iter::Accelerations` does, but is aligned to the step, and
+            // doesn't consume the first delay.
+            let acceleration = rows.last().map(|prev| {
+                let time_diff = prev.delay / 2.0 + delay / 2.0;
+                (velocity - prev.velocity) / time_diff
+            });
+            let phase = acceleration.map(Phase::from_acceleration);
+
+            rows.push(Row {
+                step: step as u32,
+                time,
+                delay,
+                velocity,
+                acceleration,
+                phase,
+            });
+
+            time += delay;
+        }
+
+        Self { rows }
+    }
+
+    /// Return the total duration of the simulated motion
+    ///
+    /// This is the sum of all delays.
+    pub fn duration(&self) -> f64 {
+        self.rows.iter().map(|row| row.delay).sum()
+    }
+
+    /// Return the highest velocity reached during the simulated motion
+    ///
+    /// Returns zero, if the simulation doesn't contain any steps.
+    pub fn peak_velocity(&self) -> f64 {
+        self.rows.iter().map(|row| row.velocity).fold(0.0, f64::max)
+    }
+
+    /// Write the simulation as CSV
+    ///
+    /// Writes a header line, followed by one line per [`Row`]. Values that are
+    /// not available (see [`Row`]) are left empty.
+    pub fn write_csv(&self, mut w: impl io::Write) -> io::Result<()> {
+        writeln!(w, "step,time,delay,velocity,acceleration,phase")?;
+
+        for row in &self.rows {
+            writeln!(
+                w,
+                "{},{},{},{},{},{}",
+                row.step,
+                row.time,
+                row.delay,
+                row.velocity,
+                Csv(row.acceleration),
+                Csv(row.phase),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Write the simulation as JSON
+    ///
+    /// Writes an array, with one object per [`Row`]. Values that are not
+    /// available (see [`Row`]) are written as `null`.
+    pub fn write_json(&self, mut w: impl io::Write) -> io::Result<()> {
+        write!(w, "[")?;
+
+        for (i, row) in self.rows.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+
+            write!(
+                w,
+                "\n  {{\"step\":{},\"time\":{},\"delay\":{},\"velocity\":{},\
+                \"acceleration\":{},\"phase\":{}}}",
+                row.step,
+                Json(Some(row.time)),
+                Json(Some(row.delay)),
+                Json(Some(row.velocity)),
+                Json(row.acceleration),
+                Json(row.phase),
+            )?;
+        }
+
+        writeln!(w, "\n]")
+    }
+}
+
+/// A single simulated step
+///
+/// Part of [`Simulation`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Row {
+    /// The index of the step, starting at zero
+    pub step: u32,
+
+    /// The point in time at which the step is made
+    ///
+    /// The first step is made at time zero.
+    pub time: f64,
+
+    /// The delay between this step and the next one
+    pub delay: f64,
+
+    /// The velocity defined by the delay
+    pub velocity: f64,
+
+    /// The acceleration between the previous step and this one
+    ///
+    /// Like [`crate::iter::Accelerations`], this assumes the velocity defined
+    /// by a delay to be reached at the mid-point of that delay. This is `None`
+    /// for the first step, as there is no previous delay to compare to.
+    pub acceleration: Option<f64>,
+
+    /// The phase of the motion, derived from the acceleration
+    ///
+    /// This is `None` for the first step, as the acceleration is not known.
+    pub phase: Option<Phase>,
+}
+
+/// The phase of a motion
+///
+/// Part of [`Row`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Phase {
+    /// The velocity is increasing
+    Accelerating,
+
+    /// The velocity is constant
+    Cruising,
+
+    /// The velocity is decreasing
+    Decelerating,
+}
+
+impl Phase {
+    fn from_acceleration(acceleration: f64) -> Self {
+        if acceleration > 0.0 {
+            Self::Accelerating
+        } else if acceleration < 0.0 {
+            Self::Decelerating
+        } else {
+            Self::Cruising
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Accelerating => "accelerating",
+            Self::Cruising => "cruising",
+            Self::Decelerating => "decelerating",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Accuracy of a motion profile, compared to a reference
+///
+/// Motion profiles that use low-accuracy numeric types might deviate from the
+/// ideal ramp, or not produce a proper ramp at all. This struct compares the
+/// output of a motion profile to a reference that uses `f64`, to help you pick
+/// the smallest numeric type that is safe for your parameters.
+///
+/// Create an instance of this struct using [`Accuracy::compare`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Accuracy {
+    /// The deviation of each step from the reference
+    ///
+    /// Only contains the steps that were produced by both the motion profile
+    /// and the reference. See [`Accuracy::outcome`] for any other steps.
+    pub steps: Vec<Deviation>,
+
+    /// The difference between the total durations of both motions
+    ///
+    /// A positive value means that the motion profile takes longer than the
+    /// reference.
+    pub time_error: f64,
+
+    /// Indicates whether the motion profile produced saturated delays
+    ///
+    /// A delay is considered saturated, if it is the maximum value of its type,
+    /// or if it is zero or less.
+    pub saturated: bool,
+
+    /// Indicates whether the motion profile produced the right number of steps
+    pub outcome: Outcome,
+}
+
+impl Accuracy {
+    /// Compare the provided motion profile to a reference
+    ///
+    /// Runs both motion profiles to completion, so both need to have entered
+    /// position mode with the same parameters beforehand. Please note that this
+    /// will never return, if either of the motion profiles produces an endless
+    /// motion.
+    ///
+    /// Please note that if a numeric type's range is too small for the motion
+    /// parameters, the motion profile's arithmetic might overflow, which causes
+    /// a panic in debug builds.
+    pub fn compare<Reference, Profile>(
+        reference: &mut Reference,
+        profile: &mut Profile,
+    ) -> Self
+    where
+        Reference: MotionProfile<Delay = f64>,
+        Profile: MotionProfile,
+        Profile::Delay: Copy + PartialEq + num_traits::Bounded + az::Cast<f64>,
+    {
+        let reference: Vec<f64> = reference.delays().collect();
+
+        let mut saturated = false;
+        let delays: Vec<f64> = profile
+            .delays()
+            .map(|delay| {
+                if delay == <Profile::Delay as num_traits::Bounded>::max_value()
+                {
+                    saturated = true;
+                }
+
+                let delay = az::cast::<_, f64>(delay);
+                if delay <= 0.0 {
+                    saturated = true;
+                }
+
+                delay
+            })
+            .collect();
+
+        let mut steps = Vec::new();
+        let mut time_error = 0.0;
+
+        for (&delay, &delay_ref) in delays.iter().zip(&reference) {
+            time_error += delay - delay_ref;
+
+            steps.push(Deviation {
+                delay_error: (delay - delay_ref) / delay_ref,
+                time_error,
+            });
+        }
+
+        // Account for any steps that are only present in one of the motions.
+        time_error += delays.iter().skip(reference.len()).sum::<f64>();
+        time_error -= reference.iter().skip(delays.len()).sum::<f64>();
+
+        let outcome = if delays.len() < reference.len() {
+            Outcome::EndedEarly {
+                missing_steps: reference.len() - delays.len(),
+            }
+        } else if delays.len() > reference.len() {
+            Outcome::Overshot {
+                extra_steps: delays.len() - reference.len(),
+            }
+        } else {
+            Outcome::Complete
+        };
+
+        Self {
+            steps,
+            time_error,
+            saturated,
+            outcome,
+        }
+    }
+
+    /// Return the largest relative delay deviation of any step
+    ///
+    /// Returns zero, if there are no steps.
+    pub fn max_delay_error(&self) -> f64 {
+        self.steps
+            .iter()
+            .map(|step| step.delay_error.abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// Indicates whether the motion profile matches the reference
+    ///
+    /// Returns `true`, if the motion profile produced the same number of steps
+    /// as the reference without saturating, and no delay deviated by more than
+    /// `max_delay_error` (a relative value, so `0.01` means 1%).
+    pub fn is_within(&self, max_delay_error: f64) -> bool {
+        !self.saturated
+            && self.outcome == Outcome::Complete
+            && self.max_delay_error() <= max_delay_error
+    }
+}
+
+/// The deviation of a single step from the reference
+///
+/// Part of [`Accuracy`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deviation {
+    /// The deviation of the delay, relative to the reference delay
+    pub delay_error: f64,
+
+    /// The cumulative time error, including this step
+    ///
+    /// This is the difference between the sum of all delays up to and
+    /// including this step, and the same sum for the reference.
+    pub time_error: f64,
+}
+
+/// Indicates whether a motion profile produced the right number of steps
+///
+/// Part of [`Accuracy`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Outcome {
+    /// The motion profile produced as many steps as the reference
+    #[default]
+    Complete,
+
+    /// The motion profile produced fewer steps than the reference
+    EndedEarly {
+        /// The number of steps missing
+        missing_steps: usize,
+    },
+
+    /// The motion profile produced more steps than the reference
+    Overshot {
+        /// The number of additional steps
+        extra_steps: usize,
+    },
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Complete => write!(f, "complete"),
+            Self::EndedEarly { missing_steps } => {
+                write!(f, "ended early ({} steps missing)", missing_steps)
+            }
+            Self::Overshot { extra_steps } => {
+                write!(f, "overshot ({} extra steps)", extra_steps)
+            }
+        }
+    }
+}
+
+/// Formats an optional value as a CSV field
+struct Csv<T>(Option<T>);
+
+impl<T> fmt::Display for Csv<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            Some(value) => write!(f, "{}", value),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats an optional value as a JSON value
+struct Json<T>(Option<T>);
+
+impl fmt::Display for Json<f64> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            // JSON can't represent infinity or NaN.
+            Some(value) if value.is_finite() => write!(f, "{}", value),
+            _ => write!(f, "null"),
+        }
+    }
+}
+
+impl fmt::Display for Json<Phase> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(phase) => write!(f, "\"{}\"", phase),
+            None => write!(f, "null"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use fixed::types::{U24F8, U32F32};
+
+    use crate::{
+        sim::{Accuracy, Outcome, Phase, Simulation},
+        MotionProfile as _, Trapezoidal,
+    };
+
+    #[test]
+    fn simulation_should_align_rows_with_steps() {
+        let mut trapezoidal = Trapezoidal::<f32>::new(6000.0);
+        trapezoidal.enter_position_mode(1000.0, 200);
+
+        let mut reference = Trapezoidal::<f32>::new(6000.0);
+        reference.enter_position_mode(1000.0, 200);
+
+        let simulation = Simulation::run(&mut trapezoidal);
+        assert_eq!(simulation.rows.len(), 200);
+
+        let mut time = 0.0;
+        for (i, (row, delay)) in
+            simulation.rows.iter().zip(reference.delays()).enumerate()
+        {
+            assert_eq!(row.step, i as u32);
+            assert_eq!(row.time, time);
+            assert_eq!(row.delay, delay as f64);
+
+            time += row.delay;
+        }
+
+        assert_eq!(simulation.duration(), time);
+        approx::assert_relative_eq!(
+            simulation.peak_velocity(),
+            1000.0,
+            max_relative = 0.001,
+        );
+
+        // The acceleration of the second row is the first value produced by
+        // `iter::Accelerations`.
+        let mut reference = Trapezoidal::<f32>::new(6000.0);
+        reference.enter_position_mode(1000.0, 200);
+        let first_accel: f32 = reference.accelerations().next().unwrap();
+
+        assert_eq!(simulation.rows[0].acceleration, None);
+        approx::assert_relative_eq!(
+            simulation.rows[1].acceleration.unwrap(),
+            first_accel as f64,
+            max_relative = 0.001,
+        );
+    }
+
+    #[test]
+    fn simulation_should_detect_phases() {
+        let mut trapezoidal = Trapezoidal::<f32>::new(6000.0);
+        trapezoidal.enter_position_mode(1000.0, 200);
+
+        let simulation = Simulation::run(&mut trapezoidal);
+
+        let mut phases = Vec::new();
+        for row in &simulation.rows {
+            if phases.last() != Some(&row.phase) {
+                phases.push(row.phase);
+            }
+        }
+
+        // The ramp ends with a few steps at the minimum velocity, which is
+        // why only the beginning is checked.
+        assert_eq!(
+            phases[..4],
+            [
+                None,
+                Some(Phase::Accelerating),
+                Some(Phase::Cruising),
+                Some(Phase::Decelerating),
+            ]
+        );
+    }
+
+    #[test]
+    fn simulation_should_export_csv() {
+        let mut trapezoidal = Trapezoidal::<f32>::new(6000.0);
+        trapezoidal.enter_position_mode(1000.0, 200);
+
+        let mut csv = Vec::new();
+        Simulation::run(&mut trapezoidal)
+            .write_csv(&mut csv)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("step,time,delay,velocity,acceleration,phase")
+        );
+        assert!(lines.next().unwrap().ends_with(",,"));
+        assert!(lines.next().unwrap().ends_with(",accelerating"));
+        assert_eq!(lines.count(), 198);
+    }
+
+    #[test]
+    fn simulation_should_export_json() {
+        let mut trapezoidal = Trapezoidal::<f32>::new(6000.0);
+        trapezoidal.enter_position_mode(1000.0, 200);
+
+        let simulation = Simulation::run(&mut trapezoidal);
+
+        let mut json = Vec::new();
+        simulation.write_json(&mut json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+
+        let rows = json.as_array().unwrap();
+        assert_eq!(rows.len(), 200);
+        assert_eq!(rows[0]["acceleration"], serde_json::Value::Null);
+        assert_eq!(rows[1]["phase"], "accelerating");
+        approx::assert_relative_eq!(
+            rows[199]["delay"].as_f64().unwrap(),
+            simulation.rows[199].delay,
+        );
+    }
+
+    #[test]
+    fn accuracy_should_report_deviation_from_reference() {
+        let mut reference = Trapezoidal::<f64>::new(6000.0);
+        reference.enter_position_mode(1000.0, 200);
+
+        let mut trapezoidal = Trapezoidal::<f64>::new(6000.0);
+        trapezoidal.enter_position_mode(1000.0, 200);
+
+        let accuracy = Accuracy::compare(&mut reference, &mut trapezoidal);
+        assert_eq!(accuracy.steps.len(), 200);
+        assert_eq!(accuracy.time_error, 0.0);
+        assert_eq!(accuracy.outcome, Outcome::Complete);
+        assert!(!accuracy.saturated);
+        assert!(accuracy.is_within(0.0));
+
+        let mut reference = Trapezoidal::<f64>::new(6000.0);
+        reference.enter_position_mode(1000.0, 200);
+
+        let mut trapezoidal =
+            Trapezoidal::<U32F32>::new(U32F32::from_num(6000));
+        trapezoidal.enter_position_mode(U32F32::from_num(1000), 200);
+
+        let accuracy = Accuracy::compare(&mut reference, &mut trapezoidal);
+        assert!(accuracy.is_within(0.001));
+    }
+
+    #[test]
+    fn accuracy_should_detect_inaccurate_types() {
+        let mut reference = Trapezoidal::<f64>::new(60.0);
+        reference.enter_position_mode(10.0, 200);
+
+        // 8 fractional bits are not nearly enough to represent these delays
+        // accurately.
+        let mut trapezoidal = Trapezoidal::<U24F8>::new(U24F8::from_num(60));
+        trapezoidal.enter_position_mode(U24F8::from_num(10), 200);
+
+        let accuracy = Accuracy::compare(&mut reference, &mut trapezoidal);
+        assert!(accuracy.max_delay_error() > 0.01);
+        assert!(!accuracy.is_within(0.01));
+    }
+}
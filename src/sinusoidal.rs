@@ -0,0 +1,344 @@
+//! Sinusoidal motion profile
+//!
+//! See [`Sinusoidal`].
+
+use core::ops;
+
+use az::Az as _;
+
+use crate::{
+    trapezoidal::Params,
+    util::{
+        ramp::{Motion, Shape},
+        sub,
+        traits::{Ceil, Sin, Sqrt},
+    },
+    MotionProfile,
+};
+
+/// Sinusoidal motion profile
+///
+/// Generates a ramp whose acceleration follows a sine half-wave, rising
+/// smoothly from zero to the peak acceleration and back to zero. This limits
+/// the jerk, which reduces the excitation of resonances compared to
+/// [`Trapezoidal`], without the complexity of a full seven-segment S-curve.
+///
+/// Ramping up to the velocity `v` with peak acceleration `a` takes the time
+/// `T = πv / 2a`. During that time, the velocity follows `v * sin²(πt / 2T)`.
+/// The time of each step is computed by solving for the position of the step
+/// using Newton's method, which requires a few evaluations of sine and cosine
+/// per step. This makes this profile considerably more expensive to compute
+/// than [`Trapezoidal`].
+///
+/// Create an instance of this struct using [`Sinusoidal::new`] or
+/// [`Sinusoidal::from_params`], then use the API defined by [`MotionProfile`]
+/// (which this struct implements) to generate the acceleration ramp.
+///
+/// # Acceleration Ramp
+///
+/// This struct will generate a ramp with the following attributes:
+/// - The velocity will always be equal to or less than the maximum velocity
+///   passed to [`MotionProfile::enter_position_mode`].
+/// - The acceleration will never exceed the peak acceleration passed to the
+///   constructor.
+/// - Deceleration mirrors acceleration.
+/// - If the motion is too short to reach the maximum velocity, the maximum
+///   velocity is reduced, so the ramp down immediately follows the ramp up.
+///
+/// Calling [`MotionProfile::enter_position_mode`] while a motion is in
+/// progress re-targets that motion, relative to the current position. If the
+/// new target is too close to stop on it, the motion comes to a stop as soon
+/// as possible instead, overshooting the target. Cutting the ramp up short
+/// like this makes the acceleration jump, but the velocity stays continuous.
+/// The velocity of a motion in progress doesn't change, so the new maximum
+/// velocity only applies to motions that start from a stand-still.
+///
+/// # Unit of Time
+///
+/// This code is agnostic on which unit of time is used. If you provide the
+/// peak acceleration and maximum velocity in steps per second, the unit of the
+/// delay returned will be seconds.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent the
+/// peak acceleration, maximum velocity, and delays per step. It is set to a
+/// 64-bit fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other type from the
+/// `fixed` crate. Please note that you need to enable support for `f32`/`f64`
+/// explicitly. Check out the section on Cargo features from the documentation
+/// in the root module for more information.
+///
+/// [`Trapezoidal`]: crate::Trapezoidal
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sinusoidal<Num = DefaultNum> {
+    peak_accel: Num,
+    motion: Option<Motion<Num>>,
+}
+
+impl<Num> Sinusoidal<Num> {
+    /// Create a new instance of `Sinusoidal`
+    ///
+    /// Accepts the peak acceleration in steps per (unit of time)^2 as an
+    /// argument. It must not be zero. See the struct documentation for
+    /// information about units of time.
+    pub fn new(peak_accel: Num) -> Self {
+        Self {
+            peak_accel,
+            motion: None,
+        }
+    }
+
+    /// Create a new instance of `Sinusoidal` from motion parameters
+    ///
+    /// Uses the target acceleration from the parameters as the peak
+    /// acceleration.
+    pub fn from_params(params: &Params<Num>) -> Self
+    where
+        Num: Copy,
+    {
+        Self::new(params.target_accel)
+    }
+}
+
+// Needed for the `MotionProfile` test suite in `crate::util::testing`.
+#[cfg(test)]
+impl Default for Sinusoidal<f32> {
+    fn default() -> Self {
+        Self::new(6000.0)
+    }
+}
+
+impl<Num> MotionProfile for Sinusoidal<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + Sqrt
+        + Sin
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        if let Some(motion) = &mut self.motion {
+            if motion.is_moving() {
+                let num_steps =
+                    if max_velocity.is_zero() { 0 } else { num_steps };
+                motion.retarget::<Sine>(num_steps);
+                return;
+            }
+        }
+
+        self.motion = if max_velocity.is_zero() || num_steps == 0 {
+            None
+        } else {
+            Some(plan(self.peak_accel, max_velocity, num_steps))
+        };
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        self.motion.as_mut()?.next_delay::<Sine>()
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.motion.as_ref().map_or(0, Motion::steps_remaining)
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        self.motion
+            .as_ref()
+            .map_or(Num::zero(), Motion::current_velocity)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.motion.as_ref().is_none_or(Motion::is_idle)
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        self.motion.as_ref().map_or(0, Motion::steps_to_stop)
+    }
+}
+
+/// The default numeric type used by [`Sinusoidal`]
+pub type DefaultNum = fixed::FixedU64<typenum::U32>;
+
+/// Plan a motion from a stand-still
+///
+/// The ramp up is parametrized by the angle `θ = πt / T`, which goes from 0 to
+/// π over the duration `T` of the ramp. The position on the ramp is
+/// `k * (θ - sin θ)`, the time is `θ * time_per_rad`.
+fn plan<Num>(peak_accel: Num, max_velocity: Num, num_steps: u32) -> Motion<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + Sqrt
+        + Sin
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    let two = Num::one() + Num::one();
+    let four = two + two;
+    let pi = pi::<Num>();
+
+    // If the ramps up and down don't fit into the motion, reduce the velocity
+    // so they fit exactly. This keeps the acceleration smooth, which cutting
+    // the ramps short wouldn't.
+    let distance = num_steps.az::<Num>();
+    let mut velocity = max_velocity;
+    if pi * velocity * velocity / (two * peak_accel) > distance {
+        velocity = (two * peak_accel * distance / pi).sqrt();
+    }
+
+    let time_per_rad = velocity / (two * peak_accel);
+    let k = velocity * velocity / (four * peak_accel);
+
+    Motion::plan::<Sine>(k, time_per_rad, pi, velocity, num_steps)
+}
+
+/// The shape of the ramp, `θ - sin θ`
+struct Sine;
+
+impl<Num> Shape<Num> for Sine
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Sin,
+{
+    fn distance(theta: Num) -> Num {
+        sub(theta, theta.sin())
+    }
+
+    fn slope(theta: Num) -> Num {
+        // The slope is `1 - cos θ`. It is computed as `2 * sin²(θ / 2)`
+        // instead, as the cosine can be negative, which unsigned types can't
+        // represent.
+        let two = Num::one() + Num::one();
+        let sin_half = (theta / two).sin();
+        two * sin_half * sin_half
+    }
+}
+
+/// Compute π for any numeric type
+///
+/// Starts with a rational approximation, which is then refined using a single
+/// Newton iteration on `sin(x) = 0`. Besides being accurate, this makes sure
+/// that the result is consistent with the type's implementation of [`Sin`].
+fn pi<Num>() -> Num
+where
+    Num: Copy + ops::Add<Output = Num> + ops::Div<Output = Num> + Sin,
+    u32: az::Cast<Num>,
+{
+    // Slightly less than π, so the sine is positive, even for unsigned types.
+    let x = 333.az::<Num>() / 106.az::<Num>();
+    x + x.sin()
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{MotionProfile as _, Sinusoidal};
+
+    #[test]
+    fn sinusoidal_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<Sinusoidal<f32>>();
+    }
+
+    #[test]
+    fn sinusoidal_should_follow_sine_acceleration() {
+        let peak_accel = 6000.0;
+        let max_velocity = 1000.0;
+
+        let mut sinusoidal = Sinusoidal::<f64>::new(peak_accel);
+        sinusoidal.enter_position_mode(max_velocity, 1000);
+
+        let pi = core::f64::consts::PI;
+        let ramp_time = pi * max_velocity / (2.0 * peak_accel);
+
+        let mut time = 0.0;
+        let mut prev: Option<(f64, f64)> = None;
+        for delay in sinusoidal.delays() {
+            let velocity = 1.0 / delay;
+            let time_mid = time + delay / 2.0;
+            time += delay;
+
+            if let Some((time_prev, velocity_prev)) = prev {
+                let accel = (velocity - velocity_prev) / (time_mid - time_prev);
+                let expected = peak_accel
+                    * (pi * (time_mid + time_prev) / 2.0 / ramp_time).sin();
+
+                if time_mid < ramp_time {
+                    assert_abs_diff_eq!(
+                        accel,
+                        expected,
+                        epsilon = peak_accel * 0.05,
+                    );
+                }
+            }
+
+            prev = Some((time_mid, velocity));
+        }
+    }
+
+    #[test]
+    fn sinusoidal_should_respect_peak_acceleration() {
+        let peak_accel = 6000.0;
+
+        for &num_steps in &[1, 2, 10, 100, 1000] {
+            let mut sinusoidal = Sinusoidal::<f64>::new(peak_accel);
+            sinusoidal.enter_position_mode(1000.0, num_steps);
+
+            for accel in sinusoidal.accelerations::<f64>() {
+                assert!(accel.abs() <= peak_accel * 1.01);
+            }
+        }
+    }
+
+    #[test]
+    fn sinusoidal_should_be_symmetric() {
+        crate::util::testing::ramp_must_be_symmetric(Sinusoidal::<f64>::new(
+            6000.0,
+        ));
+    }
+
+    #[test]
+    fn sinusoidal_should_work_with_fixed_point_numbers() {
+        use fixed::types::U32F32;
+
+        crate::util::testing::fixed_point_must_match_floating_point(
+            Sinusoidal::<f64>::new(6000.0),
+            Sinusoidal::<U32F32>::new(U32F32::from_num(6000)),
+        );
+    }
+}
@@ -0,0 +1,432 @@
+//! Motion profile based on a lookup table
+//!
+//! See [`TableProfile`].
+
+use core::ops;
+
+use az::Az as _;
+
+use crate::MotionProfile;
+
+/// Motion profile based on a lookup table
+///
+/// Follows a caller-supplied acceleration curve, given as a table of velocities
+/// or delays. This can be used to reproduce a curve that was measured on a
+/// specific machine, or one that was created by a different tool.
+///
+/// Each table entry covers a configurable number of steps. The values for the
+/// steps in between entries are interpolated linearly. The first entry is used
+/// for the first step, the last entry marks the end of the ramp. The curve is
+/// mirrored for deceleration.
+///
+/// Create an instance of this struct using [`TableProfile::from_velocities`] or
+/// [`TableProfile::from_delays`], then use the API defined by [`MotionProfile`]
+/// (which this struct implements) to generate the acceleration ramp.
+///
+/// # Acceleration Ramp
+///
+/// This struct will generate a ramp with the following attributes:
+/// - The velocity will always be equal to or less than the maximum velocity
+///   passed to [`MotionProfile::enter_position_mode`]. If the maximum velocity
+///   is reached before the end of the table, the rest of the table is ignored.
+/// - If the end of the table is reached, the motion continues with the last
+///   value from the table, until it's time to ramp down.
+/// - Deceleration mirrors acceleration, which means the ramp down takes exactly
+///   as many steps as the ramp up. If the motion is too short for the full
+///   ramp, ramp up and ramp down are truncated symmetrically.
+///
+/// # Unit of Time
+///
+/// This code is agnostic on which unit of time is used. The unit of the delays
+/// is the unit of time that was used to create the table.
+///
+/// # Type Parameter
+///
+/// The type parameter `Num` defines the type that is used to represent the
+/// table entries, maximum velocity, and delays per step. It is set to a 64-bit
+/// fixed-point number type by default.
+///
+/// You can override the default with `f32`, `f64`, or any other type from the
+/// `fixed` crate.
+///
+/// # Serialization
+///
+/// Unlike the other motion profiles, this struct doesn't implement `Serialize`
+/// and `Deserialize`, even if the **`serde`** feature is enabled. It only
+/// borrows its table, which can't be restored by deserialization.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TableProfile<Num: 'static = DefaultNum> {
+    curve: Curve<Num>,
+    steps_per_entry: u32,
+
+    delay_min: Option<Num>,
+    delay_prev: Option<Num>,
+
+    /// The number of steps on the ramp, i.e. the number of steps it would take
+    /// to reach the current velocity from stand-still
+    ramp_steps: u32,
+
+    steps_left: u32,
+}
+
+impl<Num> TableProfile<Num> {
+    /// Create a new instance of `TableProfile` from a table of velocities
+    ///
+    /// Each entry in `table` is the velocity in steps per unit of time, for the
+    /// step at the beginning of the `steps_per_entry` steps covered by the
+    /// entry. The velocities must be larger than zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `table` is empty, or `steps_per_entry` is zero.
+    pub fn from_velocities(
+        table: &'static [Num],
+        steps_per_entry: u32,
+    ) -> Self {
+        Self::new(Curve::Velocities(table), steps_per_entry)
+    }
+
+    /// Create a new instance of `TableProfile` from a table of delays
+    ///
+    /// Each entry in `table` is the delay for the step at the beginning of the
+    /// `steps_per_entry` steps covered by the entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `table` is empty, or `steps_per_entry` is zero.
+    pub fn from_delays(table: &'static [Num], steps_per_entry: u32) -> Self {
+        Self::new(Curve::Delays(table), steps_per_entry)
+    }
+
+    fn new(curve: Curve<Num>, steps_per_entry: u32) -> Self {
+        assert!(!curve.table().is_empty(), "Table must not be empty");
+        assert!(steps_per_entry > 0, "Steps per entry must not be zero");
+
+        Self {
+            curve,
+            steps_per_entry,
+
+            delay_min: None,
+            delay_prev: None,
+
+            ramp_steps: 0,
+
+            steps_left: 0,
+        }
+    }
+
+    /// Return the number of steps covered by the table
+    pub fn table_steps(&self) -> u32 {
+        let entries = self.curve.table().len() as u32;
+        (entries - 1) * self.steps_per_entry + 1
+    }
+}
+
+impl<Num> TableProfile<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    /// Compute the delay of the given step on the ramp
+    fn ramp_delay(&self, step: u32) -> Num {
+        let table = self.curve.table();
+
+        let index = (step / self.steps_per_entry) as usize;
+        let offset = step % self.steps_per_entry;
+
+        let value = if offset == 0 {
+            table[index]
+        } else {
+            let a = table[index];
+            let b = table[index + 1];
+            let factor = offset.az::<Num>() / self.steps_per_entry.az::<Num>();
+
+            // Written like this, to support unsigned types.
+            if b > a {
+                a + (b - a) * factor
+            } else {
+                a - (a - b) * factor
+            }
+        };
+
+        match self.curve {
+            Curve::Velocities(_) => value.inv(),
+            Curve::Delays(_) => value,
+        }
+    }
+}
+
+// Needed for the `MotionProfile` test suite in `crate::util::testing`.
+#[cfg(test)]
+impl Default for TableProfile<f32> {
+    fn default() -> Self {
+        static TABLE: [f32; 12] = [
+            100.0, 200.0, 300.0, 400.0, 500.0, 600.0, 700.0, 800.0, 900.0,
+            1000.0, 1100.0, 1200.0,
+        ];
+        Self::from_velocities(&TABLE, 5)
+    }
+}
+
+impl<Num> MotionProfile for TableProfile<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    type Velocity = Num;
+    type Delay = Num;
+
+    fn enter_position_mode(
+        &mut self,
+        max_velocity: Self::Velocity,
+        num_steps: u32,
+    ) {
+        self.delay_min = if max_velocity.is_zero() {
+            None
+        } else {
+            Some(max_velocity.inv())
+        };
+
+        self.steps_left = num_steps;
+    }
+
+    fn next_delay(&mut self) -> Option<Self::Delay> {
+        let delay_next = match RampMode::compute(self) {
+            RampMode::Idle => {
+                return None;
+            }
+            RampMode::RampUp { delay_next } => {
+                self.ramp_steps += 1;
+                delay_next
+            }
+            RampMode::Plateau { delay_next } => delay_next,
+            RampMode::RampDown => {
+                self.ramp_steps -= 1;
+                self.ramp_delay(self.ramp_steps)
+            }
+        };
+
+        self.delay_prev = Some(delay_next);
+        self.steps_left = self.steps_left.saturating_sub(1);
+
+        Some(delay_next)
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.steps_left
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        match self.delay_prev {
+            Some(delay_prev) if !self.is_idle() => delay_prev.inv(),
+            _ => Num::zero(),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        matches!(RampMode::compute(self), RampMode::Idle)
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        // Ramping down takes as many steps as there are on the ramp.
+        self.ramp_steps
+    }
+}
+
+/// The default numeric type used by [`TableProfile`]
+pub type DefaultNum = fixed::FixedU64<typenum::U32>;
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Curve<Num: 'static> {
+    Velocities(&'static [Num]),
+    Delays(&'static [Num]),
+}
+
+impl<Num> Curve<Num> {
+    fn table(&self) -> &'static [Num] {
+        match *self {
+            Self::Velocities(table) => table,
+            Self::Delays(table) => table,
+        }
+    }
+}
+
+enum RampMode<Num> {
+    Idle,
+    RampUp { delay_next: Num },
+    Plateau { delay_next: Num },
+    RampDown,
+}
+
+impl<Num> RampMode<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>,
+    u32: az::Cast<Num>,
+{
+    fn compute(profile: &TableProfile<Num>) -> Self {
+        let steps_left = profile.steps_left;
+        let ramp_steps = profile.ramp_steps;
+
+        if steps_left == 0 && ramp_steps == 0 {
+            return Self::Idle;
+        }
+
+        // Ramping down takes exactly as many steps as there are on the ramp.
+        // If the target step is that close, we need to decelerate.
+        if steps_left <= ramp_steps {
+            return Self::RampDown;
+        }
+
+        let delay_min = match profile.delay_min {
+            Some(delay_min) => delay_min,
+            None => {
+                // No minimum delay means someone set max velocity to zero.
+                return if ramp_steps == 0 {
+                    Self::Idle
+                } else {
+                    Self::RampDown
+                };
+            }
+        };
+
+        // If the last step on the ramp is too fast, someone lowered the
+        // maximum velocity.
+        if ramp_steps > 0 && profile.ramp_delay(ramp_steps - 1) < delay_min {
+            return Self::RampDown;
+        }
+
+        let delay_plateau = match profile.delay_prev {
+            Some(delay_prev) if ramp_steps > 0 => delay_prev,
+            _ => profile.ramp_delay(0),
+        };
+
+        // Accelerating adds a step to the ramp, which then needs an additional
+        // step to ramp down. If that doesn't fit, we need to keep the current
+        // velocity for a step.
+        if steps_left == ramp_steps + 1 {
+            return Self::Plateau {
+                delay_next: delay_plateau,
+            };
+        }
+
+        // At the end of the table, keep going at the final velocity.
+        if ramp_steps >= profile.table_steps() {
+            return Self::Plateau {
+                delay_next: delay_plateau,
+            };
+        }
+
+        let delay_next = profile.ramp_delay(ramp_steps);
+        if delay_next < delay_min {
+            return Self::Plateau {
+                delay_next: delay_min,
+            };
+        }
+
+        Self::RampUp { delay_next }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::{MotionProfile as _, TableProfile};
+
+    static VELOCITIES: [f32; 4] = [100.0, 200.0, 400.0, 800.0];
+
+    #[test]
+    fn table_profile_should_pass_motion_profile_tests() {
+        crate::util::testing::test::<TableProfile<f32>>();
+    }
+
+    #[test]
+    fn table_profile_should_interpolate_between_entries() {
+        let mut profile = TableProfile::from_velocities(&VELOCITIES, 4);
+        profile.enter_position_mode(1000.0, 100);
+
+        let velocities: Vec<f32> = profile.velocities().collect();
+
+        let expected = [
+            100.0, 125.0, 150.0, 175.0, 200.0, 250.0, 300.0, 350.0, 400.0,
+            500.0, 600.0, 700.0, 800.0,
+        ];
+        for (velocity, expected) in velocities.iter().zip(expected.iter()) {
+            assert_abs_diff_eq!(velocity, expected, epsilon = 0.01);
+        }
+
+        // The end of the table has been reached, so we keep going at the last
+        // velocity, then ramp down.
+        assert_abs_diff_eq!(velocities[50], 800.0, epsilon = 0.01);
+        for (up, down) in expected.iter().zip(velocities.iter().rev()) {
+            assert_abs_diff_eq!(up, down, epsilon = 0.01);
+        }
+    }
+
+    #[test]
+    fn table_profile_should_respect_max_velocity() {
+        let mut profile = TableProfile::from_velocities(&VELOCITIES, 4);
+        profile.enter_position_mode(300.0, 100);
+
+        let velocities: Vec<f32> = profile.velocities().collect();
+        assert_eq!(velocities.len(), 100);
+        assert_abs_diff_eq!(velocities[50], 300.0, epsilon = 0.01);
+
+        for velocity in velocities {
+            assert!(velocity <= 300.0 + 0.01);
+        }
+    }
+
+    #[test]
+    fn table_profile_should_truncate_short_moves_symmetrically() {
+        for num_steps in 1..30 {
+            let mut profile = TableProfile::from_velocities(&VELOCITIES, 4);
+            profile.enter_position_mode(1000.0, num_steps);
+
+            let delays: Vec<f32> = profile.delays().collect();
+            assert_eq!(delays.len() as u32, num_steps);
+
+            for (a, b) in delays.iter().zip(delays.iter().rev()) {
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn table_profile_should_work_with_delays_and_fixed_point_numbers() {
+        use fixed::types::U32F32;
+
+        static DELAYS: [U32F32; 3] = [
+            U32F32::from_bits(4 << 32),
+            U32F32::from_bits(2 << 32),
+            U32F32::from_bits(1 << 32),
+        ];
+
+        let mut profile = TableProfile::from_delays(&DELAYS, 2);
+        profile.enter_position_mode(U32F32::from_num(1), 8);
+
+        let delays: Vec<f32> = profile.delays().map(|d| d.to_num()).collect();
+        assert_eq!(delays, [4.0, 3.0, 2.0, 1.5, 1.5, 2.0, 3.0, 4.0]);
+    }
+}
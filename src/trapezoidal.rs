@@ -9,7 +9,7 @@ use num_traits::{clamp_max, clamp_min};
 
 use crate::{
     util::traits::{Ceil, Sqrt},
-    MotionProfile,
+    MotionProfile, TimedMoveError,
 };
 
 /// Trapezoidal motion profile
@@ -40,6 +40,17 @@ use crate::{
 /// - While ramping up or down, the acceleration will be an approximation
 ///   of the target acceleration passed to the constructor.
 ///
+/// # Resonance Bands
+///
+/// Stepper motors tend to lose torque at certain resonance velocities. Use
+/// [`Trapezoidal::add_resonance_band`] to configure up to
+/// [`MAX_RESONANCE_BANDS`] velocity bands that the motion should avoid. The
+/// motion will never stay at a velocity inside of a band:
+/// - If the maximum velocity lies inside a band, the motion will cruise at
+///   the lower bound of the band instead.
+/// - While ramping up or down, the motion will pass through the band using the
+///   elevated acceleration configured for it.
+///
 /// # Unit of Time
 ///
 /// This code is agnostic on which unit of time is used. If you provide the
@@ -66,6 +77,19 @@ use crate::{
 /// Please note that you need to enable support for `f32`/`f64` explicitly.
 /// Check out the section on Cargo features from the documentation in the root
 /// module for more information.
+///
+/// # Serialization
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`. All of the state of an ongoing motion is part of the
+/// serialized data, so a deserialized instance will continue the ramp exactly
+/// where the serialized one stopped.
+///
+/// If you want to store the configuration of a motion profile, rather than its
+/// runtime state, take a look at [`Params`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Trapezoidal<Num = DefaultNum> {
     delay_min: Option<Num>,
     delay_initial: Num,
@@ -73,6 +97,18 @@ pub struct Trapezoidal<Num = DefaultNum> {
 
     target_accel: Num,
     steps_left: u32,
+
+    /// Whether a step has been made since the motion profile was last idle
+    moving: bool,
+
+    /// The acceleration passed to the constructor, and its initial delay
+    ///
+    /// A timed motion might use a lower acceleration. The next motion that
+    /// starts from a stand-still goes back to these.
+    configured_accel: Num,
+    configured_delay_initial: Num,
+
+    bands: [Option<ResonanceBand<Num>>; MAX_RESONANCE_BANDS],
 }
 
 impl<Num> Trapezoidal<Num>
@@ -104,8 +140,80 @@ where
 
             target_accel,
             steps_left: 0,
+            moving: false,
+
+            configured_accel: target_accel,
+            configured_delay_initial: initial_delay,
+
+            bands: [None; MAX_RESONANCE_BANDS],
         }
     }
+
+    /// Create a new instance of `Trapezoidal` from the provided parameters
+    ///
+    /// The maximum velocity is not part of the parameters, as it needs to be
+    /// passed to [`MotionProfile::enter_position_mode`] for each motion.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the target acceleration is zero.
+    pub fn from_params(params: &Params<Num>) -> Self {
+        Self::new(params.target_accel)
+    }
+}
+
+impl<Num> Trapezoidal<Num>
+where
+    Num: Copy + PartialOrd + num_traits::Zero,
+{
+    /// Add a resonance band that the motion should avoid
+    ///
+    /// See the struct documentation for details on how resonance bands affect
+    /// the motion. The band takes effect immediately, for a motion that is
+    /// already in progress, too: its elevated acceleration is used for the
+    /// next step that ramps through it. The maximum velocity is only moved out
+    /// of the band with the next call to
+    /// [`MotionProfile::enter_position_mode`], so a motion that is already
+    /// cruising inside the band keeps doing so until then.
+    ///
+    /// Returns an error, if the band is invalid, overlaps with a band that has
+    /// already been added, or if [`MAX_RESONANCE_BANDS`] bands have already
+    /// been added.
+    pub fn add_resonance_band(
+        &mut self,
+        band: ResonanceBand<Num>,
+    ) -> Result<(), ResonanceBandError> {
+        // Written to also reject values that can't be compared, like NaN.
+        let valid_bounds = band.lower < band.upper;
+        if !valid_bounds {
+            return Err(ResonanceBandError::Empty);
+        }
+        let valid_accel = band.accel > Num::zero();
+        if !valid_accel {
+            return Err(ResonanceBandError::ZeroAccel);
+        }
+
+        let overlaps =
+            self.bands.iter().flatten().any(|other| {
+                band.lower < other.upper && other.lower < band.upper
+            });
+        if overlaps {
+            return Err(ResonanceBandError::Overlap);
+        }
+
+        match self.bands.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(band);
+                Ok(())
+            }
+            None => Err(ResonanceBandError::TooManyBands),
+        }
+    }
+
+    /// Remove all resonance bands
+    pub fn clear_resonance_bands(&mut self) {
+        self.bands = [None; MAX_RESONANCE_BANDS];
+    }
 }
 
 // Needed for the `MotionProfile` test suite in `crate::util::testing`.
@@ -138,6 +246,21 @@ where
         max_velocity: Self::Velocity,
         num_steps: u32,
     ) {
+        if self.is_idle() {
+            self.moving = false;
+        }
+
+        // Never cruise inside a resonance band. Clamping to one band can move
+        // the velocity into another one, hence the outer loop.
+        let mut max_velocity = max_velocity;
+        for _ in 0..MAX_RESONANCE_BANDS {
+            for band in self.bands.iter().flatten() {
+                if max_velocity > band.lower && max_velocity < band.upper {
+                    max_velocity = band.lower;
+                }
+            }
+        }
+
         // Based on equation [7] in the reference paper.
         self.delay_min = if max_velocity.is_zero() {
             None
@@ -145,11 +268,180 @@ where
             Some(max_velocity.inv())
         };
 
+        // A timed motion might have lowered the acceleration. Go back to the
+        // configured one, if we're starting from a stand-still.
+        let not_moving = self.delay_prev >= self.delay_initial;
+        if not_moving {
+            self.target_accel = self.configured_accel;
+            self.delay_initial = self.configured_delay_initial;
+            self.delay_prev = self.delay_initial;
+        }
+
         self.steps_left = num_steps;
     }
 
     fn next_delay(&mut self) -> Option<Self::Delay> {
-        let mode = RampMode::compute(self);
+        self.next_delay_with_trace().map(|(delay, _)| delay)
+    }
+
+    fn steps_remaining(&self) -> u32 {
+        self.steps_left
+    }
+
+    fn current_velocity(&self) -> Self::Velocity {
+        if !self.moving || self.is_idle() {
+            return Num::zero();
+        }
+
+        self.delay_prev.inv()
+    }
+
+    fn is_idle(&self) -> bool {
+        if RampMode::is_stopped(self) {
+            return true;
+        }
+
+        // Computing the steps to stop requires a division. Only a motion at
+        // rest, whose max velocity has been set to zero, needs them, to find
+        // out whether it still has steps to finish.
+        let not_moving = self.delay_prev >= self.delay_initial;
+        if self.delay_min.is_some() || !not_moving {
+            return false;
+        }
+
+        self.steps_left > self.compute_steps_to_stop()
+    }
+
+    fn steps_to_stop(&self) -> u32 {
+        if self.is_idle() {
+            return 0;
+        }
+
+        self.compute_steps_to_stop()
+    }
+}
+
+impl<Num> Trapezoidal<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + az::Cast<u32>
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil,
+{
+    /// Move the given number of steps in the given time
+    ///
+    /// Like [`MotionProfile::enter_position_mode`], but instead of moving as
+    /// fast as possible, solves for the maximum velocity that makes the motion
+    /// take `duration`, at the target acceleration. The maximum velocity passed
+    /// here is an upper limit for that.
+    ///
+    /// Returns an error, if the motion profile is not idle, or if the motion
+    /// can't be made in the given time without exceeding the target
+    /// acceleration or the maximum velocity. Resonance bands are not taken into
+    /// account.
+    ///
+    /// The ramp can't move slower than its first step, whose velocity depends
+    /// on the acceleration. If the duration is too long for that, the
+    /// acceleration is lowered for this motion, so the whole motion moves at
+    /// the velocity of the first step. The next motion that starts from a
+    /// stand-still uses the target acceleration again.
+    ///
+    /// The velocity is computed for a continuous ramp, corrected for the first
+    /// and last steps. Due to the approximations this motion profile makes, the
+    /// actual duration deviates from the requested one by about 1% in typical
+    /// cases, and more for very short motions.
+    pub fn enter_timed_position_mode(
+        &mut self,
+        max_velocity: Num,
+        num_steps: u32,
+        duration: Num,
+    ) -> Result<(), TimedMoveError>
+    where
+        Num: Sqrt,
+        u32: az::Cast<Num>,
+    {
+        if !self.is_idle() {
+            return Err(TimedMoveError::NotIdle);
+        }
+        if num_steps == 0 {
+            self.enter_position_mode(max_velocity, 0);
+            return Ok(());
+        }
+
+        // A motion of `S` steps that accelerates to `v` at `a`, then
+        // decelerates right away, takes the time `T = v/a + S/v`. Solved for
+        // `v`, and rearranged to avoid cancellation, that's
+        // `v = 2S / (T + sqrt(T² - 4S/a))`.
+        let two = Num::one() + Num::one();
+        let distance = num_steps.az::<Num>();
+        let mut accel = self.configured_accel;
+        let mut delay_initial = self.configured_delay_initial;
+        let min_duration_squared = two * two * distance / accel;
+
+        // From a stand-still, a continuous ramp reaches the first step after
+        // `sqrt(2/a)`. The initial delay of this ramp is `1/sqrt(2a)` (see
+        // [17] in the referenced paper), which is half of that. The ramp down
+        // mirrors this for the last step, so the continuous ramp takes up to
+        // two initial delays longer than this one. Plan the continuous ramp
+        // for the requested duration plus that difference.
+        let duration_continuous = duration + two * delay_initial;
+        let duration_squared = duration_continuous * duration_continuous;
+        if duration_squared < min_duration_squared {
+            return Err(TimedMoveError::DurationTooShort);
+        }
+
+        let root = (duration_squared - min_duration_squared).sqrt();
+        let mut velocity = two * distance / (duration_continuous + root);
+        if velocity > max_velocity {
+            return Err(TimedMoveError::DurationTooShort);
+        }
+        if velocity < delay_initial.inv() {
+            // Lowering the acceleration lowers the velocity of the first step,
+            // which is `sqrt(2a)`. The highest acceleration that is low enough
+            // makes that equal to the average velocity `S/T`, so every step
+            // takes the initial delay.
+            velocity = distance / duration;
+            accel = velocity * velocity / two;
+            delay_initial = velocity.inv();
+
+            if accel.is_zero() {
+                return Err(TimedMoveError::DurationTooLong);
+            }
+        }
+
+        self.enter_position_mode(velocity, num_steps);
+
+        self.target_accel = accel;
+        self.delay_initial = delay_initial;
+        self.delay_prev = delay_initial;
+
+        Ok(())
+    }
+
+    /// Return the next step delay, along with a trace of its computation
+    ///
+    /// Does exactly the same as [`MotionProfile::next_delay`], but additionally
+    /// returns a [`Trace`] that contains the intermediate values of the
+    /// computation. Those are computed anyway, so this doesn't add any
+    /// overhead, except for passing the trace to the caller.
+    ///
+    /// This is useful for debugging a misbehaving ramp on the target.
+    pub fn next_delay_with_trace(&mut self) -> Option<(Num, Trace<Num>)> {
+        // Computing the steps to stop requires a division, so don't bother, if
+        // there's nothing to do anyway.
+        if RampMode::is_stopped(self) {
+            return None;
+        }
+
+        let steps_to_stop = self.compute_steps_to_stop();
+        let mode = RampMode::compute(self, steps_to_stop);
 
         // Compute some basic numbers we're going to need for the following
         // calculations. All of this is statically known, so let's hope it
@@ -158,30 +450,257 @@ where
         let three = two + Num::one();
         let one_five = three / two;
 
+        let mut clamp = None;
+
         // Compute the delay for the next step. See [22] in the referenced
         // paper.
-        let q = self.target_accel * self.delay_prev * self.delay_prev;
+        let band = self.current_band(&mode);
+        let accel = band.map_or(self.target_accel, |band| band.accel);
+        let q = accel * self.delay_prev * self.delay_prev;
         let addend = one_five * q * q;
-        let delay_next = match mode {
+        let (phase, delay_next) = match mode {
             RampMode::Idle => {
                 return None;
             }
             RampMode::RampUp { delay_min } => {
-                let delay_next = self.delay_prev * (Num::one() - q + addend);
-                clamp_min(delay_next, delay_min)
+                let mut delay_next =
+                    self.delay_prev * (Num::one() - q + addend);
+
+                // Don't overshoot the band because of its higher acceleration.
+                if let Some(band) = band {
+                    delay_next = clamp_min(delay_next, band.upper.inv());
+                }
+
+                if delay_next < delay_min {
+                    clamp = Some(Clamp::MaxVelocity);
+                }
+                (Phase::RampUp, clamp_min(delay_next, delay_min))
+            }
+            RampMode::Plateau => (Phase::Plateau, self.delay_prev),
+            RampMode::RampDown => {
+                let mut delay_next =
+                    self.delay_prev * (Num::one() + q + addend);
+
+                // Don't overshoot the band because of its higher acceleration.
+                // Otherwise we'd end up with fewer steps to stop than steps
+                // left, and would accelerate again.
+                if let Some(band) = band {
+                    if !band.lower.is_zero() {
+                        delay_next = clamp_max(delay_next, band.lower.inv());
+                    }
+                }
+
+                (Phase::RampDown, delay_next)
             }
-            RampMode::Plateau => self.delay_prev,
-            RampMode::RampDown => self.delay_prev * (Num::one() + q + addend),
         };
 
         // See the explanation following [20] in the referenced paper.
+        if delay_next > self.delay_initial {
+            clamp = Some(Clamp::MinVelocity);
+        }
         let delay_next = clamp_max(delay_next, self.delay_initial);
 
         self.delay_prev = delay_next;
         self.steps_left = self.steps_left.saturating_sub(1);
+        self.moving = true;
 
-        Some(delay_next)
+        let trace = Trace {
+            phase,
+            q,
+            steps_to_stop,
+            clamp,
+        };
+
+        Some((delay_next, trace))
     }
+
+    fn compute_steps_to_stop(&self) -> u32 {
+        // Compute some basic numbers we're going to need for the following
+        // calculations. All of this is statically known, so let's hope it
+        // optimizes out.
+        let two = Num::one() + Num::one();
+
+        // Compute the number of steps needed to come to a stop. The parts of
+        // the ramp that lie within resonance bands use a different
+        // acceleration, so they need to be accounted for separately.
+        let velocity = self.delay_prev.inv();
+        let mut squared = velocity * velocity;
+        if !self.has_resonance_bands() {
+            return (squared / (two * self.target_accel)).ceil().az::<u32>();
+        }
+
+        let mut steps_in_bands = Num::zero();
+        for band in self.bands.iter().flatten() {
+            if velocity <= band.lower {
+                continue;
+            }
+
+            let upper = clamp_max(velocity, band.upper);
+            let in_band = upper * upper - band.lower * band.lower;
+
+            squared = if squared > in_band {
+                squared - in_band
+            } else {
+                Num::zero()
+            };
+            steps_in_bands = steps_in_bands + in_band / (two * band.accel);
+        }
+
+        let steps_to_stop =
+            squared / (two * self.target_accel) + steps_in_bands;
+        steps_to_stop.ceil().az::<u32>()
+    }
+
+    /// Indicate whether any resonance bands have been added
+    ///
+    /// Bands are always added to the first free slot, so it's enough to look
+    /// at the first one.
+    fn has_resonance_bands(&self) -> bool {
+        self.bands[0].is_some()
+    }
+
+    /// Return the resonance band that the next step is going to pass through
+    ///
+    /// A motion that has reached the edge of a band is still inside of it, if
+    /// it is going to pass through the band next, but not if it just left it.
+    fn current_band(&self, mode: &RampMode<Num>) -> Option<ResonanceBand<Num>> {
+        if !self.has_resonance_bands() {
+            return None;
+        }
+
+        // Only compute the velocity, if there are any bands. Otherwise, this
+        // would be a wasted division per step.
+        let mut velocity = None;
+        for band in self.bands.iter().flatten() {
+            let velocity =
+                *velocity.get_or_insert_with(|| self.delay_prev.inv());
+
+            let inside = match mode {
+                RampMode::RampUp { .. } => {
+                    velocity >= band.lower && velocity < band.upper
+                }
+                RampMode::RampDown => {
+                    velocity > band.lower && velocity <= band.upper
+                }
+                RampMode::Idle | RampMode::Plateau => false,
+            };
+            if inside {
+                return Some(*band);
+            }
+        }
+
+        None
+    }
+}
+
+/// A trace of the computation of a single step delay
+///
+/// Returned by [`Trapezoidal::next_delay_with_trace`]. This is a small,
+/// `Copy`able struct, which makes it cheap to log.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Trace<Num> {
+    /// The phase of the ramp that the step belongs to
+    pub phase: Phase,
+
+    /// The value `q` from equation \[22\] in the referenced paper
+    pub q: Num,
+
+    /// The number of steps that were needed to come to a stop
+    ///
+    /// This is computed before the step is made, and is compared to the number
+    /// of steps left, to determine whether the motion needs to ramp down.
+    pub steps_to_stop: u32,
+
+    /// The clamp that was applied to the delay, if any
+    pub clamp: Option<Clamp>,
+}
+
+/// The phase of a trapezoidal ramp
+///
+/// Part of [`Trace`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Phase {
+    /// The motion is accelerating
+    RampUp,
+
+    /// The motion is at maximum velocity
+    Plateau,
+
+    /// The motion is decelerating
+    RampDown,
+}
+
+/// A clamp that was applied to a step delay
+///
+/// Part of [`Trace`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Clamp {
+    /// The delay was clamped to not exceed the maximum velocity
+    MaxVelocity,
+
+    /// The delay was clamped to not fall below the initial velocity
+    ///
+    /// See the explanation following equation \[20\] in the referenced paper.
+    MinVelocity,
+}
+
+/// The parameters of a trapezoidal motion
+///
+/// Bundles the parameters that are typically stored as part of a machine's
+/// configuration. Use [`Trapezoidal::from_params`] to create a motion profile
+/// from them.
+///
+/// If the **`serde`** feature is enabled, this struct implements `Serialize`
+/// and `Deserialize`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Params<Num = DefaultNum> {
+    /// The target acceleration in steps per (unit of time)^2
+    pub target_accel: Num,
+}
+
+/// The maximum number of resonance bands per [`Trapezoidal`] instance
+pub const MAX_RESONANCE_BANDS: usize = 4;
+
+/// A band of velocities that a motion should avoid
+///
+/// Passed to [`Trapezoidal::add_resonance_band`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResonanceBand<Num = DefaultNum> {
+    /// The lower bound of the band in steps per unit of time
+    pub lower: Num,
+
+    /// The upper bound of the band in steps per unit of time
+    pub upper: Num,
+
+    /// The acceleration used within the band in steps per (unit of time)^2
+    ///
+    /// This should be higher than the target acceleration of the motion
+    /// profile, to pass through the band quickly.
+    pub accel: Num,
+}
+
+/// Error returned by [`Trapezoidal::add_resonance_band`]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ResonanceBandError {
+    /// The lower bound of the band is not below its upper bound
+    Empty,
+
+    /// The acceleration used within the band is not above zero
+    ZeroAccel,
+
+    /// The band overlaps with a band that has already been added
+    Overlap,
+
+    /// [`MAX_RESONANCE_BANDS`] bands have already been added
+    TooManyBands,
 }
 
 /// The default numeric type used by [`Trapezoidal`]
@@ -196,36 +715,18 @@ enum RampMode<Num> {
 
 impl<Num> RampMode<Num>
 where
-    Num: Copy
-        + PartialOrd
-        + az::Cast<u32>
-        + num_traits::One
-        + num_traits::Inv<Output = Num>
-        + ops::Add<Output = Num>
-        + ops::Div<Output = Num>
-        + Ceil,
+    Num: Copy + PartialOrd,
 {
-    fn compute(profile: &Trapezoidal<Num>) -> Self {
-        let no_steps_left = profile.steps_left == 0;
-        let not_moving = profile.delay_prev >= profile.delay_initial;
-
-        if no_steps_left && not_moving {
+    fn compute(profile: &Trapezoidal<Num>, steps_to_stop: u32) -> Self {
+        if Self::is_stopped(profile) {
             return Self::Idle;
         }
 
-        // Compute some basic numbers we're going to need for the following
-        // calculations. All of this is statically known, so let's hope it
-        // optimizes out.
-        let two = Num::one() + Num::one();
-
-        // Compute the number of steps needed to come to a stop. We'll compare
-        // that to the number of steps left to the target step below, to
-        // determine whether we need to decelerate.
-        let velocity = profile.delay_prev.inv();
-        let steps_to_stop =
-            (velocity * velocity) / (two * profile.target_accel);
-        let steps_to_stop = steps_to_stop.ceil().az::<u32>();
+        let not_moving = profile.delay_prev >= profile.delay_initial;
 
+        // Compare the number of steps needed to come to a stop to the number of
+        // steps left to the target step, to determine whether we need to
+        // decelerate.
         let target_step_is_close = profile.steps_left <= steps_to_stop;
         if target_step_is_close {
             return Self::RampDown;
@@ -254,13 +755,27 @@ where
             Self::RampUp { delay_min }
         }
     }
+
+    /// Indicate whether the motion has ended, and no new one has been started
+    fn is_stopped(profile: &Trapezoidal<Num>) -> bool {
+        let no_steps_left = profile.steps_left == 0;
+        let not_moving = profile.delay_prev >= profile.delay_initial;
+
+        no_steps_left && not_moving
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use approx::{assert_abs_diff_eq, AbsDiffEq as _};
 
-    use crate::{MotionProfile as _, Trapezoidal};
+    use crate::{
+        trapezoidal::{
+            Clamp, Phase, ResonanceBand, ResonanceBandError,
+            MAX_RESONANCE_BANDS,
+        },
+        MotionProfile as _, TimedMoveError, Trapezoidal,
+    };
 
     // The minimum velocity that is acceptable for the last step, if the goal is
     // to reach stand-still. No idea if this value is appropriate, but it
@@ -446,10 +961,319 @@ mod tests {
         assert!(decelerated);
     }
 
+    #[test]
+    fn trapezoidal_should_trace_delay_computation() {
+        let mut trapezoidal = Trapezoidal::new(6000.0);
+        trapezoidal.enter_position_mode(1000.0, 200);
+
+        let mut untraced = trapezoidal.clone();
+
+        let mut phases = Vec::new();
+        let mut clamped_to_max_velocity = false;
+
+        while let Some((delay, trace)) = trapezoidal.next_delay_with_trace() {
+            assert_eq!(Some(delay), untraced.next_delay());
+
+            if phases.last() != Some(&trace.phase) {
+                phases.push(trace.phase);
+            }
+            if trace.clamp == Some(Clamp::MaxVelocity) {
+                clamped_to_max_velocity = true;
+            }
+        }
+
+        assert_eq!(untraced.next_delay(), None);
+        assert_eq!(phases, [Phase::RampUp, Phase::Plateau, Phase::RampDown]);
+        assert!(clamped_to_max_velocity);
+    }
+
+    #[test]
+    fn trapezoidal_should_not_cruise_inside_resonance_band() {
+        let mut trapezoidal = Trapezoidal::new(6000.0);
+        trapezoidal
+            .add_resonance_band(ResonanceBand {
+                lower: 400.0,
+                upper: 600.0,
+                accel: 18000.0,
+            })
+            .unwrap();
+
+        trapezoidal.enter_position_mode(500.0, 200);
+        let velocities: Vec<f32> = trapezoidal.velocities().collect();
+
+        assert_eq!(velocities.len(), 200);
+        assert_abs_diff_eq!(velocities[100], 400.0, epsilon = 0.001);
+        for velocity in velocities {
+            assert!(velocity <= 400.0 + 0.001);
+        }
+    }
+
+    #[test]
+    fn trapezoidal_should_pass_through_resonance_band_quickly() {
+        let target_accel = 6000.0;
+        let band_accel = 18000.0;
+
+        let mut trapezoidal = Trapezoidal::new(target_accel);
+        trapezoidal
+            .add_resonance_band(ResonanceBand {
+                lower: 400.0,
+                upper: 600.0,
+                accel: band_accel,
+            })
+            .unwrap();
+
+        let num_steps = 500;
+        trapezoidal.enter_position_mode(1000.0, num_steps);
+
+        let delays: Vec<f32> = trapezoidal.delays().collect();
+        assert_eq!(delays.len() as u32, num_steps);
+
+        // Only look at steps well inside the band. The steps at the edges of
+        // the band are clamped to its bounds.
+        let inside = |delay: f32| 1.0 / delay > 410.0 && 1.0 / delay < 590.0;
+
+        let mut in_band = 0;
+        for pair in delays.windows(2) {
+            if !inside(pair[0]) || !inside(pair[1]) {
+                continue;
+            }
+
+            let accel =
+                (1.0 / pair[1] - 1.0 / pair[0]) / ((pair[0] + pair[1]) / 2.0);
+
+            assert_abs_diff_eq!(
+                accel.abs(),
+                band_accel,
+                epsilon = band_accel * 0.05,
+            );
+            in_band += 1;
+        }
+
+        // Passed through the band once while ramping up, once while ramping
+        // down.
+        assert!(in_band >= 2);
+        assert!(1.0 / delays[delays.len() - 1] <= MIN_VELOCITY);
+    }
+
+    #[test]
+    fn trapezoidal_should_reject_too_many_resonance_bands() {
+        let mut trapezoidal = Trapezoidal::new(6000.0);
+
+        for i in 0..MAX_RESONANCE_BANDS {
+            let band = ResonanceBand {
+                lower: i as f32 * 100.0,
+                upper: i as f32 * 100.0 + 50.0,
+                accel: 10000.0,
+            };
+            assert_eq!(trapezoidal.add_resonance_band(band), Ok(()));
+        }
+
+        let band = ResonanceBand {
+            lower: 900.0,
+            upper: 950.0,
+            accel: 10000.0,
+        };
+        assert_eq!(
+            trapezoidal.add_resonance_band(band),
+            Err(ResonanceBandError::TooManyBands),
+        );
+
+        trapezoidal.clear_resonance_bands();
+        assert_eq!(trapezoidal.add_resonance_band(band), Ok(()));
+    }
+
+    #[test]
+    fn trapezoidal_should_reject_invalid_resonance_bands() {
+        let mut trapezoidal = Trapezoidal::new(6000.0);
+        let band = ResonanceBand {
+            lower: 400.0,
+            upper: 600.0,
+            accel: 18000.0,
+        };
+
+        let empty = ResonanceBand {
+            lower: 600.0,
+            upper: 400.0,
+            ..band
+        };
+        assert_eq!(
+            trapezoidal.add_resonance_band(empty),
+            Err(ResonanceBandError::Empty),
+        );
+
+        let zero_accel = ResonanceBand { accel: 0.0, ..band };
+        assert_eq!(
+            trapezoidal.add_resonance_band(zero_accel),
+            Err(ResonanceBandError::ZeroAccel),
+        );
+
+        assert_eq!(trapezoidal.add_resonance_band(band), Ok(()));
+
+        let overlap = ResonanceBand {
+            lower: 500.0,
+            upper: 700.0,
+            ..band
+        };
+        assert_eq!(
+            trapezoidal.add_resonance_band(overlap),
+            Err(ResonanceBandError::Overlap),
+        );
+
+        let adjacent = ResonanceBand {
+            lower: 600.0,
+            upper: 700.0,
+            ..band
+        };
+        assert_eq!(trapezoidal.add_resonance_band(adjacent), Ok(()));
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn trapezoidal_should_implement_format() {
+        fn assert_format<T: defmt::Format>() {}
+
+        assert_format::<Trapezoidal>();
+        assert_format::<Trapezoidal<f32>>();
+        assert_format::<super::Trace<super::DefaultNum>>();
+        assert_format::<crate::iter::Accelerations<Trapezoidal, f32>>();
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn trapezoidal_should_continue_after_serialization_roundtrip() {
+        use crate::{
+            trapezoidal::{DefaultNum, Params},
+            MotionProfile,
+        };
+
+        fn check<Num>(params: Params<Num>, max_velocity: Num)
+        where
+            Num: Copy
+                + PartialEq
+                + num_traits::One
+                + core::ops::Add<Output = Num>
+                + core::ops::Div<Output = Num>
+                + crate::util::traits::Sqrt,
+            Trapezoidal<Num>: MotionProfile<Velocity = Num, Delay = Num>
+                + serde::Serialize
+                + serde::de::DeserializeOwned,
+        {
+            let mut trapezoidal = Trapezoidal::from_params(&params);
+
+            // Interrupt the motion while ramping up.
+            trapezoidal.enter_position_mode(max_velocity, 200);
+            trapezoidal.delays().take(10).for_each(drop);
+
+            let serialized = serde_json::to_string(&trapezoidal).unwrap();
+            let mut restored: Trapezoidal<Num> =
+                serde_json::from_str(&serialized).unwrap();
+
+            assert!(trapezoidal.delays().eq(restored.delays()));
+        }
+
+        check::<f32>(
+            Params {
+                target_accel: 6000.0,
+            },
+            1000.0,
+        );
+        check::<DefaultNum>(
+            Params {
+                target_accel: DefaultNum::from_num(6000),
+            },
+            DefaultNum::from_num(1000),
+        );
+    }
+
     #[derive(Debug, Eq, PartialEq)]
     enum Mode {
         RampUp,
         Plateau,
         RampDown,
     }
+
+    #[test]
+    fn trapezoidal_should_predict_steps_to_stop() {
+        let mut profile = Trapezoidal::<f32>::new(6000.0);
+
+        profile.enter_position_mode(1000.0, 500);
+        profile.delays().take(200).for_each(drop);
+
+        let steps_to_stop = profile.steps_to_stop();
+        assert!(steps_to_stop > 0);
+        assert!(profile.current_velocity() > 0.0);
+
+        profile.enter_position_mode(1000.0, 0);
+        let steps_while_stopping = profile.delays().count() as u32;
+        assert!(steps_while_stopping <= steps_to_stop);
+        assert!(steps_while_stopping + 1 >= steps_to_stop);
+        assert!(profile.is_idle());
+    }
+
+    #[test]
+    fn trapezoidal_should_take_requested_duration() {
+        for &(num_steps, duration) in
+            &[(1000, 1.0), (1000, 2.0), (100, 0.5), (10000, 5.0)]
+        {
+            let mut trapezoidal = Trapezoidal::<f64>::new(6000.0);
+            trapezoidal
+                .enter_timed_position_mode(10_000.0, num_steps, duration)
+                .unwrap();
+
+            let delays: Vec<f64> = trapezoidal.delays().collect();
+            let actual: f64 = delays.iter().sum();
+
+            assert_eq!(delays.len() as u32, num_steps);
+            assert!((actual - duration).abs() / duration < 0.01);
+        }
+    }
+
+    #[test]
+    fn trapezoidal_should_reject_infeasible_timed_motion() {
+        let mut trapezoidal = Trapezoidal::<f64>::new(6000.0);
+
+        // Would need more acceleration.
+        let result = trapezoidal.enter_timed_position_mode(10_000.0, 1000, 0.7);
+        assert_eq!(result, Err(TimedMoveError::DurationTooShort));
+
+        // Would need more velocity.
+        let result = trapezoidal.enter_timed_position_mode(1000.0, 1000, 1.0);
+        assert_eq!(result, Err(TimedMoveError::DurationTooShort));
+
+        assert_eq!(trapezoidal.next_delay(), None);
+    }
+
+    #[test]
+    fn trapezoidal_should_lower_acceleration_for_long_timed_motion() {
+        let mut trapezoidal = Trapezoidal::<f64>::new(6000.0);
+
+        // Would need to move slower than the first step of the ramp at the
+        // target acceleration.
+        trapezoidal
+            .enter_timed_position_mode(1000.0, 1000, 10.0)
+            .unwrap();
+        let delays: Vec<f64> = trapezoidal.delays().collect();
+        let actual: f64 = delays.iter().sum();
+
+        assert_eq!(delays.len(), 1000);
+        assert!((actual - 10.0).abs() / 10.0 < 0.01);
+
+        // The next motion must use the target acceleration again.
+        let mut expected = Trapezoidal::<f64>::new(6000.0);
+        expected.enter_position_mode(1000.0, 1000);
+        trapezoidal.enter_position_mode(1000.0, 1000);
+        assert!(trapezoidal.delays().eq(expected.delays()));
+    }
+
+    #[test]
+    fn trapezoidal_should_reject_timed_motion_while_moving() {
+        let mut trapezoidal = Trapezoidal::<f64>::new(6000.0);
+
+        trapezoidal.enter_position_mode(1000.0, 1000);
+        trapezoidal.delays().take(10).for_each(drop);
+
+        let result = trapezoidal.enter_timed_position_mode(1000.0, 1000, 2.0);
+        assert_eq!(result, Err(TimedMoveError::NotIdle));
+        assert_eq!(trapezoidal.delays().count(), 990);
+    }
 }
@@ -1,4 +1,23 @@
 //! Utility code for implementing motion profiles
 
+pub(crate) mod ramp;
 pub mod testing;
 pub mod traits;
+
+use core::ops;
+
+/// Subtract `b` from `a`, returning zero instead of negative results
+///
+/// Guards against rounding errors. Needed for unsigned types, where those
+/// could otherwise lead to an overflow, but also useful for signed types, where
+/// a negative time or distance makes no sense.
+pub(crate) fn sub<Num>(a: Num, b: Num) -> Num
+where
+    Num: PartialOrd + num_traits::Zero + ops::Sub<Output = Num>,
+{
+    if a > b {
+        a - b
+    } else {
+        Num::zero()
+    }
+}
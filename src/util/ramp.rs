@@ -0,0 +1,358 @@
+//! Planned motions with symmetric ramps
+//!
+//! Shared by the motion profiles that compute the time of each step from a
+//! closed-form description of their ramp, like [`Sinusoidal`]. Such a motion
+//! consists of a ramp up, an optional plateau of constant velocity, and a ramp
+//! down that mirrors the ramp up. The profiles only differ in the shape of the
+//! ramp, which is described by an implementation of [`Shape`].
+//!
+//! [`Sinusoidal`]: crate::Sinusoidal
+
+use core::ops;
+
+use az::Az as _;
+
+use super::{sub, traits::Ceil};
+
+/// The shape of a ramp
+///
+/// The ramp is parametrized by the normalized time `x`. The distance covered
+/// after `x` is `k * distance(x)`, where `k` is a scale factor chosen by the
+/// motion profile. The function must be zero, and have a slope of zero, at
+/// `x = 0`, and it must be convex for all `x` the ramp uses.
+pub(crate) trait Shape<Num> {
+    /// Return the normalized distance covered after the normalized time `x`
+    fn distance(x: Num) -> Num;
+
+    /// Return the derivative of [`Shape::distance`] at `x`
+    fn slope(x: Num) -> Num;
+}
+
+/// A planned motion
+///
+/// Positions are counted from the start of the ramp up, which might lie in the
+/// past, if the motion has been re-targeted while ramping down. The time is
+/// `x * time_scale` on the ramp up. The ramp down mirrors the ramp up.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Motion<Num> {
+    k: Num,
+    time_scale: Num,
+    max_velocity: Num,
+
+    /// The normalized time at which the full ramp ends
+    ramp_max: Num,
+
+    /// The normalized time at which the ramp ends, if cut short
+    ramp_end: Num,
+    ramp_distance: Num,
+    ramp_steps: u32,
+
+    /// The velocity of the plateau
+    velocity: Num,
+
+    num_steps: u32,
+    target: u32,
+    position: u32,
+
+    /// The normalized time at the current position, if it is on one of the
+    /// ramps
+    x: Num,
+    segment: Segment,
+
+    delay_prev: Option<Num>,
+}
+
+impl<Num> Motion<Num>
+where
+    Num: Copy
+        + PartialOrd
+        + num_traits::Zero
+        + num_traits::One
+        + num_traits::Inv<Output = Num>
+        + ops::Add<Output = Num>
+        + ops::Sub<Output = Num>
+        + ops::Mul<Output = Num>
+        + ops::Div<Output = Num>
+        + Ceil
+        + az::Cast<u32>,
+    u32: az::Cast<Num>,
+{
+    /// Plan a motion of `num_steps` steps, starting from a stand-still
+    ///
+    /// The full ramp ends at `ramp_end`. If the ramps up and down don't fit
+    /// into the motion, they are cut short, so the ramp down starts halfway
+    /// through the motion. The velocity never exceeds `max_velocity`.
+    pub fn plan<S: Shape<Num>>(
+        k: Num,
+        time_scale: Num,
+        ramp_end: Num,
+        max_velocity: Num,
+        num_steps: u32,
+    ) -> Self {
+        let mut motion = Self {
+            k,
+            time_scale,
+            max_velocity,
+
+            ramp_max: ramp_end,
+
+            ramp_end,
+            ramp_distance: Num::zero(),
+            ramp_steps: 0,
+
+            velocity: max_velocity,
+
+            num_steps,
+            target: num_steps,
+            position: 0,
+
+            x: Num::zero(),
+            segment: Segment::RampUp,
+
+            delay_prev: None,
+        };
+
+        motion.fit_ramp::<S>();
+        motion
+    }
+
+    /// Change the target of the motion to `num_steps` from the current
+    /// position
+    ///
+    /// If the target is too close to stop on it, the motion comes to a stop as
+    /// soon as possible instead, overshooting the target.
+    pub fn retarget<S: Shape<Num>>(&mut self, num_steps: u32) {
+        // While ramping down, the velocity is the same as at the mirrored
+        // position of the ramp up. If we don't need to stop, continue from
+        // there, so we can speed up again.
+        if self.segment == Segment::RampDown
+            && num_steps >= self.steps_to_stop()
+        {
+            self.position = self.num_steps - self.position;
+            self.segment = Segment::RampUp;
+        }
+
+        let steps_to_stop = self.steps_to_stop();
+        self.target = self.position.saturating_add(num_steps);
+
+        if num_steps < steps_to_stop {
+            match self.segment {
+                Segment::RampUp => {
+                    // Cut the ramp short right here. The ramp down mirrors the
+                    // part of the ramp up we've already covered.
+                    self.ramp_end = self.x;
+                    self.ramp_distance = self.position.az::<Num>();
+                    self.ramp_steps = self.position;
+                    self.num_steps = self.position * 2;
+                }
+                Segment::Plateau => {
+                    self.num_steps = self.position + self.ramp_steps;
+                }
+                Segment::RampDown => {}
+            }
+            return;
+        }
+
+        self.num_steps = self.target;
+        if self.segment == Segment::RampUp {
+            self.fit_ramp::<S>();
+        }
+    }
+
+    /// Indicate whether the motion has started and not yet ended
+    pub fn is_moving(&self) -> bool {
+        self.position > 0 && self.position < self.num_steps
+    }
+
+    /// Indicate whether the motion has ended
+    pub fn is_idle(&self) -> bool {
+        self.position >= self.num_steps
+    }
+
+    pub fn steps_remaining(&self) -> u32 {
+        self.target.saturating_sub(self.position)
+    }
+
+    pub fn current_velocity(&self) -> Num {
+        match self.delay_prev {
+            Some(delay_prev) if self.is_moving() => delay_prev.inv(),
+            _ => Num::zero(),
+        }
+    }
+
+    pub fn steps_to_stop(&self) -> u32 {
+        if !self.is_moving() {
+            return 0;
+        }
+
+        match self.segment {
+            // The ramp down mirrors the part of the ramp up we've covered.
+            Segment::RampUp => self.position,
+            Segment::Plateau => self.ramp_steps,
+            Segment::RampDown => self.num_steps - self.position,
+        }
+    }
+
+    pub fn next_delay<S: Shape<Num>>(&mut self) -> Option<Num> {
+        if self.position >= self.num_steps {
+            return None;
+        }
+
+        let num_steps = self.num_steps.az::<Num>();
+        let position_prev = self.position.az::<Num>();
+        let position = (self.position + 1).az::<Num>();
+
+        let segment = if position <= self.ramp_distance {
+            Segment::RampUp
+        } else if position + self.ramp_distance < num_steps {
+            Segment::Plateau
+        } else {
+            Segment::RampDown
+        };
+
+        let x = match segment {
+            Segment::RampUp => self.solve::<S>(position, self.x, self.ramp_end),
+            Segment::Plateau => Num::zero(),
+            Segment::RampDown => {
+                let guess = match self.segment {
+                    Segment::RampDown => self.x,
+                    _ => self.ramp_end,
+                };
+                self.solve::<S>(num_steps - position, guess, self.ramp_end)
+            }
+        };
+
+        let ramp_end = self.ramp_end;
+        let plateau_end = num_steps - self.ramp_distance;
+        let delay = match (self.segment, segment) {
+            (Segment::RampUp, Segment::RampUp) => {
+                sub(x, self.x) * self.time_scale
+            }
+            (Segment::Plateau, Segment::Plateau) => self.velocity.inv(),
+            (Segment::RampDown, Segment::RampDown) => {
+                sub(self.x, x) * self.time_scale
+            }
+            (Segment::RampUp, Segment::Plateau) => {
+                sub(ramp_end, self.x) * self.time_scale
+                    + sub(position, self.ramp_distance) / self.velocity
+            }
+            (Segment::RampUp, Segment::RampDown) => {
+                (sub(ramp_end, self.x) + sub(ramp_end, x)) * self.time_scale
+                    + sub(plateau_end, self.ramp_distance) / self.velocity
+            }
+            (Segment::Plateau, Segment::RampDown) => {
+                sub(plateau_end, position_prev) / self.velocity
+                    + sub(ramp_end, x) * self.time_scale
+            }
+            (Segment::Plateau, Segment::RampUp) | (Segment::RampDown, _) => {
+                unreachable!("Position must increase")
+            }
+        };
+
+        self.position += 1;
+        self.x = x;
+        self.segment = segment;
+
+        // Guard against rounding errors. Mathematically, the average velocity
+        // during a step can't exceed the peak velocity.
+        let delay_min = self.velocity.inv();
+        let delay = if delay < delay_min { delay_min } else { delay };
+
+        self.delay_prev = Some(delay);
+        Some(delay)
+    }
+
+    /// Fit the ramps into the motion
+    ///
+    /// Uses the full ramp, if it fits. Otherwise, the ramp is cut short, so the
+    /// ramp down starts halfway through the motion.
+    fn fit_ramp<S: Shape<Num>>(&mut self) {
+        let two = Num::one() + Num::one();
+
+        let half = self.num_steps.az::<Num>() / two;
+        let full = self.k * S::distance(self.ramp_max);
+        if full > half {
+            self.ramp_end = self.solve::<S>(half, self.ramp_max, self.ramp_max);
+            self.ramp_distance = half;
+        } else {
+            self.ramp_end = self.ramp_max;
+            self.ramp_distance = full;
+        }
+        self.ramp_steps = self.ramp_distance.ceil().az::<u32>();
+
+        // The velocity of the plateau is the velocity at the end of the ramp.
+        let velocity = self.k * S::slope(self.ramp_end) / self.time_scale;
+        self.velocity = if velocity < self.max_velocity {
+            velocity
+        } else {
+            self.max_velocity
+        };
+    }
+
+    /// Compute the normalized time at which the given distance from the start
+    /// of the ramp is reached
+    ///
+    /// Uses Newton's method to solve `k * distance(x) = distance` on
+    /// `[0, upper]`. As the function is convex, a Newton step always lands on
+    /// or above the solution, and from there, the iteration approaches the
+    /// solution monotonically, which makes it easy to detect convergence.
+    fn solve<S: Shape<Num>>(
+        &self,
+        distance: Num,
+        guess: Num,
+        upper: Num,
+    ) -> Num {
+        const MAX_ITERATIONS: usize = 64;
+
+        let two = Num::one() + Num::one();
+        let target = distance / self.k;
+
+        if target.is_zero() {
+            return Num::zero();
+        }
+
+        // The slope is zero at the start, so we can't start there.
+        let mut x = if guess.is_zero() || guess > upper {
+            upper
+        } else {
+            guess
+        };
+
+        // Make sure we start on or above the solution.
+        let value = S::distance(x);
+        let slope = S::slope(x);
+        if value < target && !slope.is_zero() {
+            let next = x + (target - value) / slope;
+            x = if next > upper { upper } else { next };
+        }
+
+        for _ in 0..MAX_ITERATIONS {
+            let value = S::distance(x);
+            let slope = S::slope(x);
+            if value <= target || slope.is_zero() {
+                break;
+            }
+
+            let step = (value - target) / slope;
+            let next = if step < x { x - step } else { x / two };
+
+            if next >= x {
+                break;
+            }
+            x = next;
+        }
+
+        x
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+enum Segment {
+    RampUp,
+    Plateau,
+    RampDown,
+}
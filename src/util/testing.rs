@@ -10,6 +10,9 @@
 
 #![cfg(test)]
 
+use approx::assert_abs_diff_eq;
+use az::Az as _;
+
 /// Alias for [`crate::MotionProfile`] with some extras, used by the tests here
 pub trait MotionProfile:
     crate::MotionProfile<Velocity = f32, Delay = f32> + Default
@@ -30,6 +33,10 @@ where
     position_mode_must_respect_maximum_velocity(Profile::default());
     position_mode_must_not_panic_because_of_zero_velocity(Profile::default());
     position_mode_must_not_panic_because_of_zero_steps(Profile::default());
+    state_queries_must_reflect_progress_of_motion(Profile::default());
+    position_mode_must_retarget_motion_in_progress(Profile::default());
+    position_mode_must_stop_motion_in_progress(Profile::default());
+    pause_and_resume_must_finish_motion(Profile::default());
 }
 
 /// A motion in position mode must produce the correct number of steps
@@ -70,3 +77,148 @@ pub fn position_mode_must_not_panic_because_of_zero_steps(
     profile.enter_position_mode(1000.0, 0);
     assert_eq!(profile.next_delay(), None);
 }
+
+/// The state queries must be consistent with the delays that are produced
+pub fn state_queries_must_reflect_progress_of_motion(
+    mut profile: impl MotionProfile,
+) {
+    assert!(profile.is_idle());
+    assert_eq!(profile.steps_remaining(), 0);
+    assert_eq!(profile.current_velocity(), 0.0);
+    assert_eq!(profile.steps_to_stop(), 0);
+
+    let max_velocity = 1000.0;
+    let num_steps = 200;
+    profile.enter_position_mode(max_velocity, num_steps);
+
+    assert!(!profile.is_idle());
+    assert_eq!(profile.steps_remaining(), num_steps);
+    assert_eq!(profile.current_velocity(), 0.0);
+
+    let mut steps_left = num_steps;
+    while profile.next_delay().is_some() {
+        steps_left -= 1;
+
+        assert_eq!(profile.steps_remaining(), steps_left);
+        assert!(profile.steps_to_stop() <= steps_left);
+        assert!(profile.current_velocity() <= max_velocity);
+        if steps_left > 0 {
+            assert!(!profile.is_idle());
+            assert!(profile.current_velocity() > 0.0);
+        }
+    }
+
+    assert_eq!(steps_left, 0);
+    assert!(profile.is_idle());
+    assert_eq!(profile.current_velocity(), 0.0);
+    assert_eq!(profile.steps_to_stop(), 0);
+}
+
+/// Entering position mode during a motion must re-target that motion
+///
+/// The new target is relative to the current position.
+pub fn position_mode_must_retarget_motion_in_progress(
+    mut profile: impl MotionProfile,
+) {
+    profile.enter_position_mode(1000.0, 200);
+    profile.delays().take(20).for_each(drop);
+
+    let num_steps = profile.steps_to_stop().max(100);
+    profile.enter_position_mode(1000.0, num_steps);
+
+    assert_eq!(profile.steps_remaining(), num_steps);
+    assert_eq!(profile.delays().count() as u32, num_steps);
+    assert!(profile.is_idle());
+}
+
+/// Entering position mode with a target of zero steps must stop the motion
+pub fn position_mode_must_stop_motion_in_progress(
+    mut profile: impl MotionProfile,
+) {
+    profile.enter_position_mode(1000.0, 200);
+    profile.delays().take(20).for_each(drop);
+
+    let steps_to_stop = profile.steps_to_stop();
+    profile.enter_position_mode(1000.0, 0);
+
+    assert!(profile.delays().count() as u32 <= steps_to_stop);
+    assert!(profile.is_idle());
+    assert_eq!(profile.steps_remaining(), 0);
+}
+
+/// A paused motion must stop, and finish on the original target when resumed
+pub fn pause_and_resume_must_finish_motion(profile: impl MotionProfile) {
+    use crate::MotionProfile as _;
+
+    let mut profile = crate::pause::Pausable::new(profile);
+
+    let num_steps = 500;
+    profile.enter_position_mode(1000.0, num_steps);
+    profile.delays().take(100).for_each(drop);
+
+    let can_pause = profile.steps_to_stop() < profile.steps_remaining();
+    profile.pause();
+    assert_eq!(profile.is_paused(), can_pause);
+
+    let steps_while_pausing = profile.delays().count() as u32;
+    if profile.is_paused() {
+        assert!(!profile.is_idle());
+        assert_eq!(
+            profile.steps_remaining(),
+            num_steps - 100 - steps_while_pausing,
+        );
+    }
+
+    profile.resume();
+    let steps_after_resuming = profile.delays().count() as u32;
+
+    assert_eq!(100 + steps_while_pausing + steps_after_resuming, num_steps);
+    assert!(profile.is_idle());
+}
+
+/// The ramp down must mirror the ramp up
+///
+/// Only applies to motion profiles with symmetric ramps, which is why it's not
+/// part of [`test`].
+pub fn ramp_must_be_symmetric<Profile>(profile: Profile)
+where
+    Profile: crate::MotionProfile<Velocity = f64, Delay = f64> + Clone,
+{
+    for num_steps in 1..50 {
+        let mut profile = profile.clone();
+        profile.enter_position_mode(1000.0, num_steps);
+
+        let delays: Vec<f64> = profile.delays().collect();
+        assert_eq!(delays.len() as u32, num_steps);
+
+        for (a, b) in delays.iter().zip(delays.iter().rev()) {
+            assert_abs_diff_eq!(a, b, epsilon = 1e-9);
+        }
+    }
+}
+
+/// A fixed-point motion profile must produce the same delays as a
+/// floating-point one
+///
+/// Both motion profiles must be configured the same way. Not part of [`test`],
+/// which only uses `f32`.
+pub fn fixed_point_must_match_floating_point<Float, Fixed>(
+    mut float: Float,
+    mut fixed: Fixed,
+) where
+    Float: crate::MotionProfile<Velocity = f64, Delay = f64>,
+    Fixed: crate::MotionProfile,
+    Fixed::Delay: az::Cast<f64>,
+    u32: az::Cast<Fixed::Velocity>,
+{
+    let num_steps = 500;
+    float.enter_position_mode(1000.0, num_steps);
+    fixed.enter_position_mode(1000.az::<Fixed::Velocity>(), num_steps);
+
+    let mut count = 0;
+    for (float, fixed) in float.delays().zip(fixed.delays()) {
+        assert_abs_diff_eq!(fixed.az::<f64>(), float, epsilon = 1e-6);
+        count += 1;
+    }
+    assert_eq!(count, num_steps);
+}
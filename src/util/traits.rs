@@ -24,6 +24,38 @@ pub trait Ceil {
     fn ceil(self) -> Self;
 }
 
+/// Defines an interface to the sine function
+pub trait Sin {
+    /// Return the sine of `self`, which is an angle in radians
+    fn sin(self) -> Self;
+}
+
+/// Defines an interface to the cosine function
+pub trait Cos {
+    /// Return the cosine of `self`, which is an angle in radians
+    fn cos(self) -> Self;
+}
+
+/// Defines an interface to the two-argument arctangent
+pub trait Atan2 {
+    /// Return the angle of the point (`other`, `self`) in radians
+    ///
+    /// The result is in the range `[-π, π]`.
+    fn atan2(self, other: Self) -> Self;
+}
+
+/// Defines an interface to the exponential function
+pub trait Exp {
+    /// Return `e^self`
+    fn exp(self) -> Self;
+}
+
+/// Defines an interface to the natural logarithm
+pub trait Ln {
+    /// Return the natural logarithm of `self`
+    fn ln(self) -> Self;
+}
+
 #[cfg(any(test, feature = "std"))]
 mod impl_using_std {
     impl super::Sqrt for f32 {
@@ -38,6 +70,36 @@ mod impl_using_std {
         }
     }
 
+    impl super::Sin for f32 {
+        fn sin(self) -> Self {
+            f32::sin(self)
+        }
+    }
+
+    impl super::Cos for f32 {
+        fn cos(self) -> Self {
+            f32::cos(self)
+        }
+    }
+
+    impl super::Atan2 for f32 {
+        fn atan2(self, other: Self) -> Self {
+            f32::atan2(self, other)
+        }
+    }
+
+    impl super::Exp for f32 {
+        fn exp(self) -> Self {
+            f32::exp(self)
+        }
+    }
+
+    impl super::Ln for f32 {
+        fn ln(self) -> Self {
+            f32::ln(self)
+        }
+    }
+
     impl super::Sqrt for f64 {
         fn sqrt(self) -> Self {
             f64::sqrt(self)
@@ -49,6 +111,36 @@ mod impl_using_std {
             f64::ceil(self)
         }
     }
+
+    impl super::Sin for f64 {
+        fn sin(self) -> Self {
+            f64::sin(self)
+        }
+    }
+
+    impl super::Cos for f64 {
+        fn cos(self) -> Self {
+            f64::cos(self)
+        }
+    }
+
+    impl super::Atan2 for f64 {
+        fn atan2(self, other: Self) -> Self {
+            f64::atan2(self, other)
+        }
+    }
+
+    impl super::Exp for f64 {
+        fn exp(self) -> Self {
+            f64::exp(self)
+        }
+    }
+
+    impl super::Ln for f64 {
+        fn ln(self) -> Self {
+            f64::ln(self)
+        }
+    }
 }
 
 #[cfg(all(not(test), not(feature = "std"), feature = "libm"))]
@@ -65,6 +157,36 @@ mod impl_using_libm {
         }
     }
 
+    impl super::Sin for f32 {
+        fn sin(self) -> Self {
+            libm::sinf(self)
+        }
+    }
+
+    impl super::Cos for f32 {
+        fn cos(self) -> Self {
+            libm::cosf(self)
+        }
+    }
+
+    impl super::Atan2 for f32 {
+        fn atan2(self, other: Self) -> Self {
+            libm::atan2f(self, other)
+        }
+    }
+
+    impl super::Exp for f32 {
+        fn exp(self) -> Self {
+            libm::expf(self)
+        }
+    }
+
+    impl super::Ln for f32 {
+        fn ln(self) -> Self {
+            libm::logf(self)
+        }
+    }
+
     impl super::Sqrt for f64 {
         fn sqrt(self) -> Self {
             libm::sqrt(self)
@@ -76,10 +198,46 @@ mod impl_using_libm {
             libm::ceil(self)
         }
     }
+
+    impl super::Sin for f64 {
+        fn sin(self) -> Self {
+            libm::sin(self)
+        }
+    }
+
+    impl super::Cos for f64 {
+        fn cos(self) -> Self {
+            libm::cos(self)
+        }
+    }
+
+    impl super::Atan2 for f64 {
+        fn atan2(self, other: Self) -> Self {
+            libm::atan2(self, other)
+        }
+    }
+
+    impl super::Exp for f64 {
+        fn exp(self) -> Self {
+            libm::exp(self)
+        }
+    }
+
+    impl super::Ln for f64 {
+        fn ln(self) -> Self {
+            libm::log(self)
+        }
+    }
 }
 
 mod impl_fixed {
-    use fixed::types::extra::{LeEqU128, LeEqU16, LeEqU32, LeEqU64, LeEqU8};
+    use fixed::{
+        consts,
+        types::{
+            extra::{LeEqU128, LeEqU16, LeEqU32, LeEqU64, LeEqU8},
+            I32F32,
+        },
+    };
     use fixed_sqrt::{
         traits::{IsEven, LtU128, LtU16, LtU32, LtU64, LtU8},
         FixedSqrt,
@@ -150,4 +308,302 @@ mod impl_fixed {
             Sqrt => (LtU128)
             Ceil => (LeEqU128)
     );
+
+    macro_rules! impl_fixed_transcendental {
+        ($($num:ident: $bound:ident)*) => {
+            $(
+                impl<U> super::Sin for fixed::$num<U>
+                where
+                    U: $bound,
+                {
+                    fn sin(self) -> Self {
+                        Self::saturating_from_num(sin(
+                            I32F32::saturating_from_num(self),
+                        ))
+                    }
+                }
+
+                impl<U> super::Cos for fixed::$num<U>
+                where
+                    U: $bound,
+                {
+                    fn cos(self) -> Self {
+                        let x = I32F32::saturating_from_num(self);
+                        let frac_pi_2 = I32F32::from_num(consts::FRAC_PI_2);
+
+                        Self::saturating_from_num(sin(
+                            x.saturating_add(frac_pi_2),
+                        ))
+                    }
+                }
+
+                impl<U> super::Atan2 for fixed::$num<U>
+                where
+                    U: $bound,
+                {
+                    fn atan2(self, other: Self) -> Self {
+                        Self::saturating_from_num(atan2(
+                            I32F32::saturating_from_num(self),
+                            I32F32::saturating_from_num(other),
+                        ))
+                    }
+                }
+
+                impl<U> super::Exp for fixed::$num<U>
+                where
+                    U: $bound,
+                {
+                    fn exp(self) -> Self {
+                        Self::saturating_from_num(exp(
+                            I32F32::saturating_from_num(self),
+                        ))
+                    }
+                }
+
+                impl<U> super::Ln for fixed::$num<U>
+                where
+                    U: $bound,
+                {
+                    fn ln(self) -> Self {
+                        Self::saturating_from_num(ln(
+                            I32F32::saturating_from_num(self),
+                        ))
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_fixed_transcendental!(
+        FixedU8: LeEqU8
+        FixedU16: LeEqU16
+        FixedU32: LeEqU32
+        FixedU64: LeEqU64
+        FixedU128: LeEqU128
+        FixedI8: LeEqU8
+        FixedI16: LeEqU16
+        FixedI32: LeEqU32
+        FixedI64: LeEqU64
+        FixedI128: LeEqU128
+    );
+
+    /// Compute the sine of `x`
+    ///
+    /// The `fixed` crate doesn't provide trigonometric functions, so all types
+    /// are converted to `I32F32`, which has enough precision for the purposes
+    /// of this library, and more than enough range for the angles involved.
+    /// Results that can't be represented by the original type (like negative
+    /// values for unsigned types) saturate.
+    fn sin(x: I32F32) -> I32F32 {
+        let pi = I32F32::from_num(consts::PI);
+        let frac_pi_2 = I32F32::from_num(consts::FRAC_PI_2);
+        let tau = I32F32::from_num(consts::TAU);
+
+        // Reduce the angle to [-pi, pi], then to [-pi/2, pi/2], using the
+        // symmetry of the sine around pi/2.
+        let mut x = x - tau * (x / tau).round();
+        if x > frac_pi_2 {
+            x = pi - x;
+        }
+        if x < -frac_pi_2 {
+            x = -pi - x;
+        }
+
+        // Taylor series, evaluated using Horner's method. Within the reduced
+        // range, the error of the last term is below 1e-9.
+        let x2 = x * x;
+        let mut result = I32F32::ONE;
+        for divisor in [156, 110, 72, 42, 20, 6].iter() {
+            result = I32F32::ONE - x2 / *divisor * result;
+        }
+
+        x * result
+    }
+
+    /// Compute the angle of the point (`x`, `y`)
+    ///
+    /// Like [`sin`], this is computed using `I32F32`. Returns zero, if both
+    /// arguments are zero.
+    fn atan2(y: I32F32, x: I32F32) -> I32F32 {
+        let pi = I32F32::from_num(consts::PI);
+        let frac_pi_2 = I32F32::from_num(consts::FRAC_PI_2);
+
+        let abs_x = x.saturating_abs();
+        let abs_y = y.saturating_abs();
+        if abs_x == I32F32::ZERO && abs_y == I32F32::ZERO {
+            return I32F32::ZERO;
+        }
+
+        // Reduce the problem to `atan(z)` with `0 <= z <= 1`, then use
+        // `atan(z) = pi/4 + atan((z - 1) / (z + 1))` to reduce the argument
+        // further, to at most `tan(pi/8)` in magnitude.
+        let z = if abs_y <= abs_x {
+            abs_y / abs_x
+        } else {
+            abs_x / abs_y
+        };
+        let tan_frac_pi_8 = I32F32::from_num(consts::SQRT_2) - I32F32::ONE;
+        let (offset, w) = if z > tan_frac_pi_8 {
+            (
+                I32F32::from_num(consts::FRAC_PI_4),
+                (z - I32F32::ONE) / (z + I32F32::ONE),
+            )
+        } else {
+            (I32F32::ZERO, z)
+        };
+
+        // Taylor series, evaluated using Horner's method. Within the reduced
+        // range, the error of the last term is below 1e-10.
+        let w2 = w * w;
+        let mut result = I32F32::ZERO;
+        for divisor in [23, 21, 19, 17, 15, 13, 11, 9, 7, 5, 3, 1].iter() {
+            result = I32F32::ONE / *divisor - w2 * result;
+        }
+        let mut angle = offset + w * result;
+
+        if abs_y > abs_x {
+            angle = frac_pi_2 - angle;
+        }
+        if x < I32F32::ZERO {
+            angle = pi - angle;
+        }
+        if y < I32F32::ZERO {
+            angle = -angle;
+        }
+
+        angle
+    }
+
+    /// Compute `e^x`
+    ///
+    /// Like [`sin`], this is computed using `I32F32`. Results that are out of
+    /// range saturate.
+    fn exp(x: I32F32) -> I32F32 {
+        let ln_2 = I32F32::from_num(consts::LN_2);
+
+        // Split `x` into `k * ln(2) + r`, with `0 <= r < ln(2)`. Then
+        // `e^x = 2^k * e^r`.
+        let k = (x / ln_2).floor();
+        let r = x - k * ln_2;
+        let k = k.to_num::<i32>();
+
+        if k >= 31 {
+            return I32F32::MAX;
+        }
+        if k <= -33 {
+            return I32F32::ZERO;
+        }
+
+        // Taylor series, evaluated using Horner's method. Within the reduced
+        // range, the error of the last term is below 1e-10.
+        let mut result = I32F32::ONE;
+        for divisor in [12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1].iter() {
+            result = I32F32::ONE + r / *divisor * result;
+        }
+
+        if k >= 0 {
+            result.saturating_mul_int(1 << k)
+        } else {
+            result >> -k as u32
+        }
+    }
+
+    /// Compute the natural logarithm of `x`
+    ///
+    /// Like [`sin`], this is computed using `I32F32`. The logarithm of zero or
+    /// of negative numbers saturates to the minimum value.
+    fn ln(x: I32F32) -> I32F32 {
+        if x <= I32F32::ZERO {
+            return I32F32::MIN;
+        }
+
+        let ln_2 = I32F32::from_num(consts::LN_2);
+
+        // Split `x` into `2^k * m`, with `1 <= m < 2`. Then
+        // `ln(x) = k * ln(2) + ln(m)`.
+        let k = x.int_log2();
+        let m = if k >= 0 {
+            x >> k as u32
+        } else {
+            x << -k as u32
+        };
+
+        // Series of `ln(m) = 2 * atanh(z)`, with `z = (m - 1) / (m + 1)`,
+        // evaluated using Horner's method. As `0 <= z < 1/3`, the error of the
+        // last term is below 1e-9.
+        let z = (m - I32F32::ONE) / (m + I32F32::ONE);
+        let z2 = z * z;
+        let mut result = I32F32::ZERO;
+        for divisor in [17, 15, 13, 11, 9, 7, 5, 3, 1].iter() {
+            result = result * z2 + I32F32::from_num(2) / *divisor;
+        }
+
+        result * z + ln_2 * i64::from(k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+    use fixed::types::{I32F32, U32F32};
+
+    use super::{Atan2 as _, Cos as _, Exp as _, Ln as _, Sin as _};
+
+    #[test]
+    fn fixed_sin_and_cos_should_match_floating_point() {
+        for i in -100..=100 {
+            let x = i as f64 * 0.1;
+
+            let sin = I32F32::from_num(x).sin().to_num::<f64>();
+            let cos = I32F32::from_num(x).cos().to_num::<f64>();
+
+            assert_abs_diff_eq!(sin, x.sin(), epsilon = 1e-8);
+            assert_abs_diff_eq!(cos, x.cos(), epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn fixed_sin_and_cos_should_saturate_for_unsigned_types() {
+        let x = U32F32::from_num(4.0);
+
+        assert_eq!(x.sin(), U32F32::ZERO);
+        assert_abs_diff_eq!(x.cos().to_num::<f64>(), 0.0, epsilon = 1e-8);
+    }
+
+    #[test]
+    fn fixed_atan2_should_match_floating_point() {
+        for i in -20..=20 {
+            for j in -20..=20 {
+                let (y, x) = (i as f64 * 0.5, j as f64 * 0.5);
+
+                let atan2 = I32F32::from_num(y)
+                    .atan2(I32F32::from_num(x))
+                    .to_num::<f64>();
+
+                assert_abs_diff_eq!(atan2, y.atan2(x), epsilon = 1e-8);
+            }
+        }
+    }
+
+    #[test]
+    fn fixed_exp_and_ln_should_match_floating_point() {
+        for i in -100..=100 {
+            let x = i as f64 * 0.1;
+
+            let exp = I32F32::from_num(x).exp().to_num::<f64>();
+            assert_abs_diff_eq!(exp, x.exp(), epsilon = x.exp() * 1e-8 + 1e-9);
+
+            let y = I32F32::from_num(x.exp());
+            let ln = y.ln().to_num::<f64>();
+            assert_abs_diff_eq!(ln, y.to_num::<f64>().ln(), epsilon = 1e-8);
+        }
+    }
+
+    #[test]
+    fn fixed_exp_and_ln_should_saturate() {
+        assert_eq!(I32F32::from_num(100).exp(), I32F32::MAX);
+        assert_eq!(I32F32::from_num(-100).exp(), I32F32::ZERO);
+        assert_eq!(I32F32::ZERO.ln(), I32F32::MIN);
+        assert_eq!(U32F32::from_num(0.5).ln(), U32F32::ZERO);
+    }
 }